@@ -0,0 +1,274 @@
+//! Compares the event throughput and latency of the engine with a replica of
+//! its previous design, an `mpsc` channel and a mutex guarded listener map.
+//!
+//! Run with `cargo bench --features headless`.
+
+extern crate rust_graphics_window as window;
+
+use {
+    std::{
+        collections::{BTreeMap, LinkedList},
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+            Arc, Mutex, RwLock, Weak,
+        },
+        thread::{spawn, yield_now, JoinHandle},
+        time::{Duration, Instant},
+    },
+    window::{
+        event::{Data, DispatchMode, Event, Listener, ListenerHandle, Move},
+        headless::window::{Config, Window},
+    },
+};
+
+const LISTENERS: usize = 8;
+const BATCH: u64 = 1000;
+const BATCHES: u64 = 200;
+const LATENCY_SAMPLES: u64 = 20_000;
+
+/// Counts the events and sums their latencies in nanoseconds.
+#[derive(Default)]
+struct Counter {
+    events: AtomicU64,
+    latency: AtomicU64,
+}
+
+impl Counter {
+    fn count(&self, e: &Event) {
+        self.latency
+            .fetch_add(e.get_time().elapsed().as_nanos() as u64, Ordering::Relaxed);
+        self.events.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn wait_for(&self, events: u64) {
+        while self.events.load(Ordering::Relaxed) < events {
+            yield_now();
+        }
+    }
+}
+
+struct CountingListener(Arc<Counter>);
+
+impl Listener for CountingListener {
+    fn on_event(&mut self, e: &Event) -> bool {
+        self.0.count(e);
+        false
+    }
+}
+
+fn mouse_move(previous: (i64, i64), current: (i64, i64)) -> Data {
+    Data::Move(Move::Mouse {
+        previous,
+        current,
+        delta: (current.0 - previous.0, current.1 - previous.1),
+        normalized_previous: (0.0, 0.0),
+        normalized_current: (0.0, 0.0),
+        normalized_delta: (0.0, 0.0),
+    })
+}
+
+type Listeners = Mutex<BTreeMap<i64, LinkedList<Weak<RwLock<dyn Listener>>>>>;
+
+/// The previous design of the engine.
+struct Baseline {
+    listeners: Arc<Listeners>,
+    receiver: Arc<Mutex<Receiver<Event>>>,
+    sender: Sender<Event>,
+    state: Mutex<(i64, i64)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Baseline {
+    fn new(threaded: bool) -> Self {
+        let (sender, receiver) = channel();
+        let mut result = Self {
+            listeners: Arc::new(Mutex::new(BTreeMap::new())),
+            receiver: Arc::new(Mutex::new(receiver)),
+            sender,
+            state: Mutex::new((0, 0)),
+            thread: None,
+        };
+        if threaded {
+            let listeners = result.listeners.clone();
+            let receiver = result.receiver.clone();
+            result.thread = Some(spawn(move || loop {
+                let e = match receiver
+                    .lock()
+                    .unwrap()
+                    .recv_timeout(Duration::from_millis(100))
+                {
+                    Ok(e) => e,
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                };
+                if let &Data::Terminate = e.get_data() {
+                    return;
+                }
+                Self::dispatch_event(&listeners, &e);
+            }));
+        }
+        result
+    }
+
+    fn add(&self, l: Weak<RwLock<dyn Listener>>) {
+        self.listeners
+            .lock()
+            .unwrap()
+            .entry(0)
+            .or_default()
+            .push_back(l);
+    }
+
+    fn set_mouse_position(&self, current: (i64, i64)) {
+        let data = {
+            let mut state = self.state.lock().unwrap();
+            let d = mouse_move(*state, current);
+            *state = current;
+            d
+        };
+        self.sender.send(Event::new(data)).unwrap();
+    }
+
+    fn dispatch(&self) {
+        let receiver = self.receiver.lock().unwrap();
+        while let Ok(e) = receiver.try_recv() {
+            Self::dispatch_event(&self.listeners, &e);
+        }
+    }
+
+    fn dispatch_event(listeners: &Listeners, e: &Event) {
+        let listeners = listeners.lock().unwrap();
+        'listeners_loop: for ls in listeners.values() {
+            for l in ls {
+                if let Some(l) = l.upgrade() {
+                    if l.write().unwrap().on_event(e) {
+                        break 'listeners_loop;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Baseline {
+    fn drop(&mut self) {
+        if let Some(t) = self.thread.take() {
+            self.sender.send(Event::new(Data::Terminate)).unwrap();
+            t.join().unwrap();
+        }
+    }
+}
+
+fn new_window(dispatch_mode: DispatchMode) -> Arc<Window> {
+    Window::new_with_config(Config {
+        dispatch_mode,
+        ..Config::default()
+    })
+}
+
+fn add_counters(w: &Window, counter: &Arc<Counter>) -> Vec<ListenerHandle> {
+    (0..LISTENERS)
+        .map(|_| {
+            let c = counter.clone();
+            w.get_event_engine().on(0, move |e| {
+                c.count(e);
+                false
+            })
+        })
+        .collect()
+}
+
+fn add_baseline_counters(b: &Baseline, counter: &Arc<Counter>) -> Vec<Arc<RwLock<dyn Listener>>> {
+    (0..LISTENERS)
+        .map(|_| {
+            let l: Arc<RwLock<dyn Listener>> =
+                Arc::new(RwLock::new(CountingListener(counter.clone())));
+            b.add(Arc::downgrade(&l));
+            l
+        })
+        .collect()
+}
+
+fn report(name: &str, counter: &Counter, events: u64, elapsed: Duration) {
+    let calls = counter.events.load(Ordering::Relaxed);
+    println!(
+        "{:<32} {:>12.0} events/s {:>10.0} ns mean latency",
+        name,
+        events as f64 / elapsed.as_secs_f64(),
+        counter.latency.load(Ordering::Relaxed) as f64 / calls.max(1) as f64,
+    );
+}
+
+fn throughput() {
+    let counter = Arc::new(Counter::default());
+    let w = new_window(DispatchMode::CallerThread);
+    let _handles = add_counters(&w, &counter);
+    let start = Instant::now();
+    for b in 0..BATCHES {
+        for i in 0..BATCH {
+            w.inject_mouse_move((b * BATCH + i) as i64 % 1000, 0);
+        }
+        w.fetch_events();
+    }
+    report(
+        "engine, caller thread",
+        &counter,
+        BATCH * BATCHES,
+        start.elapsed(),
+    );
+
+    let counter = Arc::new(Counter::default());
+    let baseline = Baseline::new(false);
+    let _listeners = add_baseline_counters(&baseline, &counter);
+    let start = Instant::now();
+    for b in 0..BATCHES {
+        for i in 0..BATCH {
+            baseline.set_mouse_position(((b * BATCH + i) as i64 % 1000, 0));
+        }
+        baseline.dispatch();
+    }
+    report(
+        "baseline, caller thread",
+        &counter,
+        BATCH * BATCHES,
+        start.elapsed(),
+    );
+}
+
+fn latency() {
+    let counter = Arc::new(Counter::default());
+    let w = new_window(DispatchMode::Threaded);
+    let _handles = add_counters(&w, &counter);
+    let start = Instant::now();
+    for i in 0..LATENCY_SAMPLES {
+        w.inject_mouse_move(i as i64 % 1000, 0);
+        counter.wait_for((i + 1) * LISTENERS as u64);
+    }
+    report(
+        "engine, threaded",
+        &counter,
+        LATENCY_SAMPLES,
+        start.elapsed(),
+    );
+
+    let counter = Arc::new(Counter::default());
+    let baseline = Baseline::new(true);
+    let _listeners = add_baseline_counters(&baseline, &counter);
+    let start = Instant::now();
+    for i in 0..LATENCY_SAMPLES {
+        baseline.set_mouse_position((i as i64 % 1000, 0));
+        counter.wait_for((i + 1) * LISTENERS as u64);
+    }
+    report(
+        "baseline, threaded",
+        &counter,
+        LATENCY_SAMPLES,
+        start.elapsed(),
+    );
+}
+
+fn main() {
+    throughput();
+    latency();
+}
@@ -3,34 +3,29 @@ extern crate rust_graphics_main as main;
 extern crate rust_graphics_window as window;
 
 use {
-    log::{log_i, result_f},
+    log::log_i,
     main::{main, Arg},
-    std::sync::{Arc, RwLock},
+    std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
 };
 
-struct Listener {
-    pub running: bool,
-}
-
-impl window::event::Listener for Listener {
-    fn on_event(&mut self, event: &window::event::Event) -> bool {
+fn start(arg: Arg) {
+    let w = window::Window::new(arg);
+    let running = Arc::new(AtomicBool::new(true));
+    let r = running.clone();
+    let _handle = w.get_event_engine().on(0, move |event| {
         match event.get_data() {
-            &window::event::Data::Quit => self.running = false,
+            &window::event::Data::Quit => r.store(false, Ordering::Relaxed),
             _e @ _ => {
                 #[cfg(feature = "verbose-log")]
                 log_i!("{:?}", _e);
             }
         }
-        return false;
-    }
-}
-
-fn start(arg: Arg) {
-    let w = window::Window::new(arg);
-    let listener = Arc::new(RwLock::new(Listener { running: true }));
-    let l: Arc<RwLock<dyn window::event::Listener>> = listener.clone();
-    w.get_event_engine().add(0, Arc::downgrade(&l));
-    while { result_f!(listener.read()).running } {
+        false
+    });
+    while running.load(Ordering::Relaxed) {
         w.fetch_events();
     }
     log_i!("Program ended.");
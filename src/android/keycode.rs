@@ -0,0 +1,86 @@
+//! Key codes and meta states of the NDK's `android/keycodes.h` and
+//! `android/input.h`.
+
+pub(super) const BACK: i32 = 4;
+pub(super) const NUM_0: i32 = 7;
+pub(super) const NUM_9: i32 = 16;
+pub(super) const STAR: i32 = 17;
+pub(super) const DPAD_UP: i32 = 19;
+pub(super) const DPAD_DOWN: i32 = 20;
+pub(super) const DPAD_LEFT: i32 = 21;
+pub(super) const DPAD_RIGHT: i32 = 22;
+pub(super) const DPAD_CENTER: i32 = 23;
+pub(super) const VOLUME_UP: i32 = 24;
+pub(super) const VOLUME_DOWN: i32 = 25;
+pub(super) const A: i32 = 29;
+pub(super) const Z: i32 = 54;
+pub(super) const COMMA: i32 = 55;
+pub(super) const PERIOD: i32 = 56;
+pub(super) const ALT_LEFT: i32 = 57;
+pub(super) const ALT_RIGHT: i32 = 58;
+pub(super) const SHIFT_LEFT: i32 = 59;
+pub(super) const SHIFT_RIGHT: i32 = 60;
+pub(super) const TAB: i32 = 61;
+pub(super) const SPACE: i32 = 62;
+pub(super) const ENTER: i32 = 66;
+pub(super) const DEL: i32 = 67;
+pub(super) const GRAVE: i32 = 68;
+pub(super) const MINUS: i32 = 69;
+pub(super) const EQUALS: i32 = 70;
+pub(super) const LEFT_BRACKET: i32 = 71;
+pub(super) const RIGHT_BRACKET: i32 = 72;
+pub(super) const BACKSLASH: i32 = 73;
+pub(super) const SEMICOLON: i32 = 74;
+pub(super) const APOSTROPHE: i32 = 75;
+pub(super) const SLASH: i32 = 76;
+pub(super) const PLUS: i32 = 81;
+pub(super) const MENU: i32 = 82;
+pub(super) const MEDIA_PLAY_PAUSE: i32 = 85;
+pub(super) const MEDIA_STOP: i32 = 86;
+pub(super) const MEDIA_NEXT: i32 = 87;
+pub(super) const MEDIA_PREVIOUS: i32 = 88;
+pub(super) const MEDIA_REWIND: i32 = 89;
+pub(super) const MEDIA_FAST_FORWARD: i32 = 90;
+pub(super) const PAGE_UP: i32 = 92;
+pub(super) const PAGE_DOWN: i32 = 93;
+pub(super) const ESCAPE: i32 = 111;
+pub(super) const FORWARD_DEL: i32 = 112;
+pub(super) const CTRL_LEFT: i32 = 113;
+pub(super) const CTRL_RIGHT: i32 = 114;
+pub(super) const CAPS_LOCK: i32 = 115;
+pub(super) const SCROLL_LOCK: i32 = 116;
+pub(super) const META_LEFT: i32 = 117;
+pub(super) const META_RIGHT: i32 = 118;
+pub(super) const SYSRQ: i32 = 120;
+pub(super) const BREAK: i32 = 121;
+pub(super) const MOVE_HOME: i32 = 122;
+pub(super) const MOVE_END: i32 = 123;
+pub(super) const INSERT: i32 = 124;
+pub(super) const MEDIA_PLAY: i32 = 126;
+pub(super) const MEDIA_PAUSE: i32 = 127;
+pub(super) const F1: i32 = 131;
+pub(super) const F12: i32 = 142;
+pub(super) const NUM_LOCK: i32 = 143;
+pub(super) const NUMPAD_0: i32 = 144;
+pub(super) const NUMPAD_9: i32 = 153;
+pub(super) const NUMPAD_DIVIDE: i32 = 154;
+pub(super) const NUMPAD_MULTIPLY: i32 = 155;
+pub(super) const NUMPAD_SUBTRACT: i32 = 156;
+pub(super) const NUMPAD_ADD: i32 = 157;
+pub(super) const NUMPAD_DOT: i32 = 158;
+pub(super) const NUMPAD_ENTER: i32 = 160;
+pub(super) const VOLUME_MUTE: i32 = 164;
+
+pub(super) const ACTION_DOWN: i32 = 0;
+pub(super) const ACTION_UP: i32 = 1;
+/// Repeated presses of a key, the repeat count is the number of the presses.
+pub(super) const ACTION_MULTIPLE: i32 = 2;
+
+pub(super) const META_ALT_LEFT_ON: i32 = 0x10;
+pub(super) const META_ALT_RIGHT_ON: i32 = 0x20;
+pub(super) const META_SHIFT_LEFT_ON: i32 = 0x40;
+pub(super) const META_SHIFT_RIGHT_ON: i32 = 0x80;
+pub(super) const META_CTRL_LEFT_ON: i32 = 0x2000;
+pub(super) const META_CTRL_RIGHT_ON: i32 = 0x4000;
+pub(super) const META_META_LEFT_ON: i32 = 0x20000;
+pub(super) const META_META_RIGHT_ON: i32 = 0x40000;
@@ -1,3 +1,4 @@
 extern crate rust_graphics_android as android;
 
+pub(crate) mod keycode;
 pub(crate) mod window;
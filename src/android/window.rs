@@ -1,12 +1,14 @@
 use {
     super::{
-        super::event::{Engine as EventEngine, FingerIndexType},
+        super::event::{Button, Engine as EventEngine, FingerIndexType, Keyboard, Lifecycle},
         android::{
+            configuration,
             glue::{AndroidApp, AndroidPollSource, AppCmd},
             input,
             looper::ALooper_pollAll,
             window,
         },
+        keycode,
     },
     log::{log_i, result_f, unexpected_f},
     std::{
@@ -17,9 +19,30 @@ use {
     },
 };
 
+#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "debug-derive", derive(Debug))]
+pub enum Orientation {
+    Portrait,
+    Landscape,
+    Square,
+    Unknown,
+}
+
+/// Distances of the content area from the edges of the window in pixels,
+/// they are covered by the system bars.
+#[derive(Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "debug-derive", derive(Debug))]
+pub struct Insets {
+    pub left: i64,
+    pub top: i64,
+    pub right: i64,
+    pub bottom: i64,
+}
+
 struct State {
     focused: bool,
     paused: bool,
+    back_consumed: bool,
 }
 
 pub struct Window {
@@ -42,6 +65,7 @@ impl Window {
             state: Mutex::new(State {
                 paused: false,
                 focused: false,
+                back_consumed: false,
             }),
             event_engine: EventEngine::new(),
         });
@@ -56,6 +80,57 @@ impl Window {
         &self.event_engine
     }
 
+    pub fn request_redraw(&self) {
+        self.event_engine.request_redraw();
+    }
+
+    pub fn get_orientation(&self) -> Orientation {
+        match unsafe { configuration::AConfiguration_getOrientation(self.android_app.config) } {
+            configuration::ACONFIGURATION_ORIENTATION_PORT => Orientation::Portrait,
+            configuration::ACONFIGURATION_ORIENTATION_LAND => Orientation::Landscape,
+            configuration::ACONFIGURATION_ORIENTATION_SQUARE => Orientation::Square,
+            _ => Orientation::Unknown,
+        }
+    }
+
+    pub fn get_content_insets(&self) -> Insets {
+        let r = &self.android_app.content_rect;
+        if self.android_app.window.is_null() || r.right <= r.left || r.bottom <= r.top {
+            return Insets::default();
+        }
+        let (width, height) = self.get_window_size();
+        Insets {
+            left: r.left as i64,
+            top: r.top as i64,
+            right: width - r.right as i64,
+            bottom: height - r.bottom as i64,
+        }
+    }
+
+    fn get_window_size(&self) -> (i64, i64) {
+        unsafe {
+            (
+                window::ANativeWindow_getWidth(self.android_app.window) as i64,
+                window::ANativeWindow_getHeight(self.android_app.window) as i64,
+            )
+        }
+    }
+
+    /// The window may have a new size after the rotation or a new surface.
+    fn update_window_size(&self) {
+        if self.android_app.window.is_null() {
+            return;
+        }
+        let (w, h) = self.get_window_size();
+        self.event_engine.window_size_changed(w, h);
+    }
+
+    /// When the Back button is consumed, the app receives it and the system
+    /// does not finish the activity.
+    pub fn set_back_consumed(&self, consumed: bool) {
+        result_f!(self.state.lock()).back_consumed = consumed;
+    }
+
     pub fn get_window(&self) -> *mut window::ANativeWindow {
         self.android_app.window
     }
@@ -66,6 +141,7 @@ impl Window {
             log_i!("Android app has been terminated already waiting for main loop to terminate.");
             return;
         }
+        self.event_engine.start_frame();
         let timeout: c_int = if result_f!(self.state.lock()).paused {
             10
         } else {
@@ -81,6 +157,7 @@ impl Window {
                 ((*source).process)(transmute_copy(&self.android_app), source);
             }
         }
+        self.event_engine.dispatch();
     }
 
     fn initialize(&self) {
@@ -98,11 +175,9 @@ impl Window {
                     let state = result_f!(self.state.lock());
                     !state.paused && state.focused
                 } {
-                    let w =
-                        unsafe { window::ANativeWindow_getWidth(self.android_app.window) } as i64;
-                    let h =
-                        unsafe { window::ANativeWindow_getHeight(self.android_app.window) } as i64;
-                    self.event_engine.init_window_aspects(w, h);
+                    let (w, h) = self.get_window_size();
+                    self.event_engine
+                        .init_window_aspects(w, h, self.get_scale_factor());
                     return;
                 }
             }
@@ -115,11 +190,17 @@ impl Window {
             AppCmd::InitWindow => {
                 #[cfg(feature = "verbose-log")]
                 log_i!("Window has been shown!");
+                self.update_window_size();
+                self.event_engine
+                    .lifecycle_changed(Lifecycle::SurfaceCreated);
             }
             AppCmd::TermWindow => {
                 #[cfg(feature = "verbose-log")]
                 log_i!("Window has been terminated.");
-                self.event_engine.quit();
+                // The glue destroys the window after this returns, so it
+                // waits for the listeners.
+                self.event_engine
+                    .lifecycle_changed(Lifecycle::SurfaceDestroyed);
             }
             AppCmd::GainedFocus => {
                 #[cfg(feature = "verbose-log")]
@@ -137,6 +218,7 @@ impl Window {
                 #[cfg(feature = "verbose-log")]
                 log_i!("Android app has been paused.");
                 result_f!(self.state.lock()).paused = true;
+                self.event_engine.lifecycle_changed(Lifecycle::Suspended);
             }
             AppCmd::Start => {
                 #[cfg(feature = "verbose-log")]
@@ -146,17 +228,42 @@ impl Window {
                 #[cfg(feature = "verbose-log")]
                 log_i!("Android app has been resumed.");
                 result_f!(self.state.lock()).paused = false;
+                self.event_engine.lifecycle_changed(Lifecycle::Resumed);
+            }
+            AppCmd::ConfigChanged => {
+                #[cfg(feature = "verbose-log")]
+                log_i!("Android app configuration has been changed.");
+                self.event_engine
+                    .scale_factor_changed(self.get_scale_factor());
+                self.update_window_size();
             }
             AppCmd::SaveState => {
                 #[cfg(feature = "verbose-log")]
                 log_i!("Android app should save its state.");
+                self.event_engine.lifecycle_changed(Lifecycle::SaveState);
             }
             AppCmd::Stop => {
                 #[cfg(feature = "verbose-log")]
                 log_i!("Android app has been stoped.");
             }
+            AppCmd::WindowResized => {
+                #[cfg(feature = "verbose-log")]
+                log_i!("Window has been resized.");
+                self.update_window_size();
+            }
+            AppCmd::ContentRectChanged => {
+                #[cfg(feature = "verbose-log")]
+                log_i!("Content rectangle has been changed.");
+                self.update_window_size();
+            }
+            AppCmd::LowMemory => {
+                #[cfg(feature = "verbose-log")]
+                log_i!("Android app is low on memory.");
+                self.event_engine.lifecycle_changed(Lifecycle::LowMemory);
+            }
             AppCmd::Destroy => {
                 log_i!("Android app has been destroyed.");
+                self.event_engine.quit();
             }
             _c @ _ => {
                 #[cfg(feature = "verbose-log")]
@@ -168,37 +275,264 @@ impl Window {
     fn handle_input(&self, e: *mut input::AInputEvent) -> i32 {
         let event_type = unsafe { input::AInputEvent_getType(e) };
         if event_type & input::AInputEventType::Motion as i32 != 0 {
-            let event_action = unsafe { input::AMotionEvent_getAction(e) };
-            let action: input::AMotionEventAction = unsafe { transmute(event_action & 0xFF) };
-            let pointer_index = ((event_action & 0xFF00) >> 8) as usize;
-            let finger_index =
-                unsafe { input::AMotionEvent_getPointerId(e, pointer_index) } as FingerIndexType;
-            let pointer_x = unsafe { input::AMotionEvent_getRawX(e, pointer_index) } as i64;
-            let pointer_y = unsafe { input::AMotionEvent_getRawY(e, pointer_index) } as i64;
-            match action {
-                input::AMotionEventAction::PointerDown | input::AMotionEventAction::Down => {
-                    self.event_engine
-                        .finger_down(pointer_x, pointer_y, finger_index);
-                }
-                input::AMotionEventAction::PointerUp | input::AMotionEventAction::Up => {
-                    self.event_engine
-                        .finger_up(pointer_x, pointer_y, finger_index);
-                }
-                input::AMotionEventAction::Move => {
-                    self.event_engine
-                        .finger_move(pointer_x, pointer_y, finger_index);
-                }
-                _ => (),
-            }
+            self.handle_motion(e);
         } else if event_type & input::AInputEventType::Key as i32 != 0 {
-            #[cfg(feature = "verbose-log")]
-            log_i!("Unhandled");
+            return self.handle_key(e);
         } else {
             unexpected_f!();
         }
         0
     }
 
+    /// Coordinates are relative to the window, a `Move` carries every pointer
+    /// and the samples batched since the previous event.
+    fn handle_motion(&self, e: *mut input::AInputEvent) {
+        let event_action = unsafe { input::AMotionEvent_getAction(e) };
+        let action: input::AMotionEventAction = unsafe { transmute(event_action & 0xFF) };
+        let pointer_index = ((event_action & 0xFF00) >> 8) as usize;
+        let pointers_count = unsafe { input::AMotionEvent_getPointerCount(e) };
+        match action {
+            input::AMotionEventAction::PointerDown | input::AMotionEventAction::Down => {
+                let (x, y, index, pressure, size) = Self::get_pointer(e, pointer_index);
+                self.event_engine.finger_down(x, y, index, pressure, size);
+            }
+            input::AMotionEventAction::PointerUp | input::AMotionEventAction::Up => {
+                let (x, y, index, pressure, size) = Self::get_pointer(e, pointer_index);
+                self.event_engine.finger_up(x, y, index, pressure, size);
+            }
+            input::AMotionEventAction::Move => {
+                let history_size = unsafe { input::AMotionEvent_getHistorySize(e) };
+                for h in 0..history_size {
+                    for p in 0..pointers_count {
+                        let (x, y, index, pressure, size) = Self::get_historical_pointer(e, p, h);
+                        self.event_engine.finger_move(x, y, index, pressure, size);
+                    }
+                }
+                for p in 0..pointers_count {
+                    let (x, y, index, pressure, size) = Self::get_pointer(e, p);
+                    self.event_engine.finger_move(x, y, index, pressure, size);
+                }
+            }
+            input::AMotionEventAction::Cancel => {
+                for p in 0..pointers_count {
+                    let (x, y, index, pressure, size) = Self::get_pointer(e, p);
+                    self.event_engine.finger_cancel(x, y, index, pressure, size);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn get_pointer(e: *mut input::AInputEvent, p: usize) -> (i64, i64, FingerIndexType, f64, f64) {
+        unsafe {
+            (
+                input::AMotionEvent_getX(e, p) as i64,
+                input::AMotionEvent_getY(e, p) as i64,
+                input::AMotionEvent_getPointerId(e, p) as FingerIndexType,
+                input::AMotionEvent_getPressure(e, p) as f64,
+                input::AMotionEvent_getSize(e, p) as f64,
+            )
+        }
+    }
+
+    fn get_historical_pointer(
+        e: *mut input::AInputEvent,
+        p: usize,
+        h: usize,
+    ) -> (i64, i64, FingerIndexType, f64, f64) {
+        unsafe {
+            (
+                input::AMotionEvent_getHistoricalX(e, p, h) as i64,
+                input::AMotionEvent_getHistoricalY(e, p, h) as i64,
+                input::AMotionEvent_getPointerId(e, p) as FingerIndexType,
+                input::AMotionEvent_getHistoricalPressure(e, p, h) as f64,
+                input::AMotionEvent_getHistoricalSize(e, p, h) as f64,
+            )
+        }
+    }
+
+    /// Returns 1 when the key is consumed, the system keys like the volume
+    /// keys are reported but still handled by the system.
+    fn handle_key(&self, e: *mut input::AInputEvent) -> i32 {
+        let code = unsafe { input::AKeyEvent_getKeyCode(e) };
+        let action = unsafe { input::AKeyEvent_getAction(e) };
+        let repeat = unsafe { input::AKeyEvent_getRepeatCount(e) };
+        let meta = unsafe { input::AKeyEvent_getMetaState(e) };
+        let key = Self::translate_key(code);
+        self.sync_modifiers(meta, &key);
+        let button = Button::Keyboard(key.clone());
+        match action {
+            keycode::ACTION_DOWN => self.event_engine.button_pressed(button),
+            keycode::ACTION_UP => self.event_engine.button_released(button),
+            keycode::ACTION_MULTIPLE => {
+                for _ in 0..repeat {
+                    self.event_engine.button_pressed(button.clone());
+                    self.event_engine.button_released(button.clone());
+                }
+            }
+            _a => {
+                #[cfg(feature = "verbose-log")]
+                log_i!("Unexpected key action: {}", _a);
+                return 0;
+            }
+        }
+        match key {
+            Keyboard::Back => result_f!(self.state.lock()).back_consumed as i32,
+            Keyboard::VolumeUp
+            | Keyboard::VolumeDown
+            | Keyboard::VolumeMute
+            | Keyboard::MediaPlayPause
+            | Keyboard::MediaPlay
+            | Keyboard::MediaPause
+            | Keyboard::MediaStop
+            | Keyboard::MediaNext
+            | Keyboard::MediaPrevious
+            | Keyboard::MediaRewind
+            | Keyboard::MediaFastForward
+            | Keyboard::Unknown(_) => 0,
+            _ => 1,
+        }
+    }
+
+    /// Soft keyboards may send the modified keys without the modifier key
+    /// events, so the modifiers follow the meta state of each key event. The
+    /// event's own key is skipped, it is pressed or released by the event.
+    fn sync_modifiers(&self, meta: i32, key: &Keyboard) {
+        let modifiers = [
+            (keycode::META_SHIFT_LEFT_ON, Keyboard::Shift(0)),
+            (keycode::META_SHIFT_RIGHT_ON, Keyboard::Shift(1)),
+            (keycode::META_CTRL_LEFT_ON, Keyboard::Control(0)),
+            (keycode::META_CTRL_RIGHT_ON, Keyboard::Control(1)),
+            (keycode::META_ALT_LEFT_ON, Keyboard::Alt(0)),
+            (keycode::META_ALT_RIGHT_ON, Keyboard::Alt(1)),
+            (keycode::META_META_LEFT_ON, Keyboard::Super(0)),
+            (keycode::META_META_RIGHT_ON, Keyboard::Super(1)),
+        ];
+        for &(mask, ref modifier) in &modifiers {
+            if modifier != key {
+                self.event_engine
+                    .sync_button(Button::Keyboard(modifier.clone()), meta & mask != 0);
+            }
+        }
+    }
+
+    fn translate_key(code: i32) -> Keyboard {
+        match code {
+            keycode::A..=keycode::Z => [
+                Keyboard::A,
+                Keyboard::B,
+                Keyboard::C,
+                Keyboard::D,
+                Keyboard::E,
+                Keyboard::F,
+                Keyboard::G,
+                Keyboard::H,
+                Keyboard::I,
+                Keyboard::J,
+                Keyboard::K,
+                Keyboard::L,
+                Keyboard::M,
+                Keyboard::N,
+                Keyboard::O,
+                Keyboard::P,
+                Keyboard::Q,
+                Keyboard::R,
+                Keyboard::S,
+                Keyboard::T,
+                Keyboard::U,
+                Keyboard::V,
+                Keyboard::W,
+                Keyboard::X,
+                Keyboard::Y,
+                Keyboard::Z,
+            ][(code - keycode::A) as usize]
+                .clone(),
+            keycode::NUM_0..=keycode::NUM_9 => Keyboard::Number {
+                number: code - keycode::NUM_0,
+                pad: false,
+            },
+            keycode::NUMPAD_0..=keycode::NUMPAD_9 => Keyboard::Number {
+                number: code - keycode::NUMPAD_0,
+                pad: true,
+            },
+            keycode::F1..=keycode::F12 => Keyboard::Function((code - keycode::F1 + 1) as u8),
+            keycode::BACK => Keyboard::Back,
+            keycode::STAR | keycode::NUMPAD_MULTIPLY => Keyboard::Star,
+            keycode::DPAD_UP => Keyboard::ArrowUp,
+            keycode::DPAD_DOWN => Keyboard::ArrowDown,
+            keycode::DPAD_LEFT => Keyboard::ArrowLeft,
+            keycode::DPAD_RIGHT => Keyboard::ArrowRight,
+            keycode::DPAD_CENTER => Keyboard::Enter(1),
+            keycode::VOLUME_UP => Keyboard::VolumeUp,
+            keycode::VOLUME_DOWN => Keyboard::VolumeDown,
+            keycode::VOLUME_MUTE => Keyboard::VolumeMute,
+            keycode::COMMA => Keyboard::Comma(0),
+            keycode::PERIOD => Keyboard::Dot(0),
+            keycode::NUMPAD_DOT => Keyboard::Dot(1),
+            keycode::ALT_LEFT => Keyboard::Alt(0),
+            keycode::ALT_RIGHT => Keyboard::Alt(1),
+            keycode::SHIFT_LEFT => Keyboard::Shift(0),
+            keycode::SHIFT_RIGHT => Keyboard::Shift(1),
+            keycode::CTRL_LEFT => Keyboard::Control(0),
+            keycode::CTRL_RIGHT => Keyboard::Control(1),
+            keycode::META_LEFT => Keyboard::Super(0),
+            keycode::META_RIGHT => Keyboard::Super(1),
+            keycode::TAB => Keyboard::Tab,
+            keycode::SPACE => Keyboard::Space(0),
+            keycode::ENTER => Keyboard::Enter(0),
+            keycode::NUMPAD_ENTER => Keyboard::Enter(1),
+            keycode::DEL => Keyboard::Backspace,
+            keycode::FORWARD_DEL => Keyboard::Delete,
+            keycode::GRAVE => Keyboard::BackQuote,
+            keycode::MINUS => Keyboard::Minus(0),
+            keycode::NUMPAD_SUBTRACT => Keyboard::Minus(1),
+            keycode::PLUS => Keyboard::Plus(0),
+            keycode::NUMPAD_ADD => Keyboard::Plus(1),
+            keycode::EQUALS => Keyboard::Equal,
+            keycode::LEFT_BRACKET => Keyboard::BracketLeft,
+            keycode::RIGHT_BRACKET => Keyboard::BracketRight,
+            keycode::BACKSLASH => Keyboard::BackSlash(0),
+            keycode::SEMICOLON => Keyboard::SemiColon,
+            keycode::APOSTROPHE => Keyboard::Quote,
+            keycode::SLASH => Keyboard::Slash(0),
+            keycode::NUMPAD_DIVIDE => Keyboard::Slash(1),
+            keycode::MENU => Keyboard::Menu(0),
+            keycode::MEDIA_PLAY_PAUSE => Keyboard::MediaPlayPause,
+            keycode::MEDIA_PLAY => Keyboard::MediaPlay,
+            keycode::MEDIA_PAUSE => Keyboard::MediaPause,
+            keycode::MEDIA_STOP => Keyboard::MediaStop,
+            keycode::MEDIA_NEXT => Keyboard::MediaNext,
+            keycode::MEDIA_PREVIOUS => Keyboard::MediaPrevious,
+            keycode::MEDIA_REWIND => Keyboard::MediaRewind,
+            keycode::MEDIA_FAST_FORWARD => Keyboard::MediaFastForward,
+            keycode::PAGE_UP => Keyboard::PageUp,
+            keycode::PAGE_DOWN => Keyboard::PageDown,
+            keycode::ESCAPE => Keyboard::Escape(0),
+            keycode::CAPS_LOCK => Keyboard::CapsLock(0),
+            keycode::SCROLL_LOCK => Keyboard::ScrollLock,
+            keycode::SYSRQ => Keyboard::PrintScreen,
+            keycode::BREAK => Keyboard::PauseBreak,
+            keycode::MOVE_HOME => Keyboard::Home,
+            keycode::MOVE_END => Keyboard::End,
+            keycode::INSERT => Keyboard::Insert,
+            keycode::NUM_LOCK => Keyboard::NumLock,
+            c => {
+                #[cfg(feature = "verbose-log")]
+                log_i!("Unknown key code: {}", c);
+                Keyboard::Unknown(c as u32)
+            }
+        }
+    }
+
+    fn get_scale_factor(&self) -> f64 {
+        let density = unsafe { configuration::AConfiguration_getDensity(self.android_app.config) };
+        match density {
+            configuration::ACONFIGURATION_DENSITY_DEFAULT
+            | configuration::ACONFIGURATION_DENSITY_NONE => 1.0,
+            d => d as f64 / configuration::ACONFIGURATION_DENSITY_MEDIUM as f64,
+        }
+    }
+
     // pub fn get_window_aspect_ratio(&self) -> f32 {
     //     1.7
     // }
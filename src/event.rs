@@ -1,21 +1,76 @@
+use crate::gesture::Recognizer;
+use arc_swap::{ArcSwap, ArcSwapOption};
+use bitflags::bitflags;
+use crossbeam_queue::ArrayQueue;
 #[cfg(feature = "verbose-log")]
 use log::log_i;
-use log::{result_f, unwrap_f};
+use log::{log_e, result_f, unwrap_f};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
 use std::{
-    collections::{BTreeMap, BTreeSet, LinkedList},
+    cmp::{max, min},
+    collections::{BTreeMap, BTreeSet, VecDeque},
     sync::{
-        atomic::{AtomicU64, Ordering},
-        mpsc::{channel, Sender},
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{channel, Receiver, Sender, TryIter},
         Arc, Mutex, RwLock, Weak,
     },
-    thread::{spawn, JoinHandle},
-    time::{Duration, Instant},
+    thread::{self, park_timeout, spawn, JoinHandle, Thread},
+    time::Duration,
+    vec::IntoIter,
 };
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
 
 pub type FingerIndexType = i64;
+pub type GamepadId = u32;
+
+/// Instants are stored as their age in seconds at the time of serialization.
+#[cfg(feature = "serde")]
+mod serde_instant {
+    use super::Instant;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(i: &Instant, s: S) -> Result<S::Ok, S::Error> {
+        i.elapsed().as_secs_f64().serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Instant, D::Error> {
+        let age = f64::deserialize(d)?;
+        let now = Instant::now();
+        Ok(now.checked_sub(Duration::from_secs_f64(age)).unwrap_or(now))
+    }
+}
+
+/// Barrel buttons are numbered from 1.
+#[cfg_attr(feature = "debug-derive", derive(Debug))]
+#[derive(PartialEq, PartialOrd, Eq, Ord, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PenButton {
+    Tip,
+    Barrel(u32),
+}
+
+/// A change of a pen as the platforms report it, the fields are the same as
+/// the `Data::Pen` ones.
+#[cfg_attr(feature = "debug-derive", derive(Debug))]
+#[derive(Clone)]
+pub struct PenState {
+    pub position: (i64, i64),
+    pub pressure: f64,
+    pub tilt: (f64, f64),
+    pub rotation: f64,
+    pub button: Option<(PenButton, ButtonAction)>,
+    pub eraser: bool,
+    pub proximity: bool,
+}
 
 #[cfg_attr(feature = "debug-derive", derive(Debug))]
 #[derive(PartialEq, PartialOrd, Eq, Ord, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Mouse {
     Left,
     Right,
@@ -28,6 +83,7 @@ pub enum Mouse {
 
 #[cfg_attr(feature = "debug-derive", derive(Debug))]
 #[derive(PartialEq, PartialOrd, Eq, Ord, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Keyboard {
     A,
     B,
@@ -61,7 +117,10 @@ pub enum Keyboard {
     ScrollLock,
     PauseBreak,
     BackQuote,
-    Number { number: i32, pad: bool },
+    Number {
+        number: i32,
+        pad: bool,
+    },
     Backspace,
     Delete,
     Insert,
@@ -97,33 +156,134 @@ pub enum Keyboard {
     ArrowRight,
     Equal,
     Menu(u8),
+    /// The back button of the Android devices.
+    Back,
+    VolumeUp,
+    VolumeDown,
+    VolumeMute,
+    MediaPlayPause,
+    MediaPlay,
+    MediaPause,
+    MediaStop,
+    MediaNext,
+    MediaPrevious,
+    MediaRewind,
+    MediaFastForward,
+    Unknown(u32),
+}
+
+/// Buttons of the standard gamepad layout, face buttons are named by their
+/// positions (e.g. `South` is A on the Xbox and Cross on the PlayStation).
+#[cfg_attr(feature = "debug-derive", derive(Debug))]
+#[derive(PartialEq, PartialOrd, Eq, Ord, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum GamepadButton {
+    South,
+    East,
+    West,
+    North,
+    LeftBumper,
+    RightBumper,
+    LeftTrigger,
+    RightTrigger,
+    Select,
+    Start,
+    Mode,
+    LeftThumb,
+    RightThumb,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+    Unknown(u32),
+}
+
+/// Sticks are in [-1, 1] with the positive values to the right and down,
+/// triggers are in [0, 1].
+#[cfg_attr(feature = "debug-derive", derive(Debug))]
+#[derive(PartialEq, PartialOrd, Eq, Ord, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum GamepadAxis {
+    LeftX,
+    LeftY,
+    RightX,
+    RightY,
+    LeftTrigger,
+    RightTrigger,
     Unknown(u32),
 }
 
 #[cfg_attr(feature = "debug-derive", derive(Debug))]
 #[derive(PartialEq, PartialOrd, Eq, Ord, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Button {
     Mouse(Mouse),
     Keyboard(Keyboard),
+    Gamepad {
+        id: GamepadId,
+        button: GamepadButton,
+    },
 }
 
 #[derive(Clone, Copy)]
 #[cfg_attr(feature = "debug-derive", derive(Debug))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct WindowSizeChange {
     pub current: WindowAspects,
     pub previous: WindowAspects,
     pub delta: WindowAspects,
 }
 
+/// A rectangle in physical pixels of the window.
+#[derive(Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "debug-derive", derive(Debug))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Rect {
+    pub position: (i64, i64),
+    pub size: (i64, i64),
+}
+
+impl Rect {
+    pub fn new(x: i64, y: i64, width: i64, height: i64) -> Self {
+        Self {
+            position: (x, y),
+            size: (width, height),
+        }
+    }
+
+    pub fn union(&self, o: &Self) -> Self {
+        let x = min(self.position.0, o.position.0);
+        let y = min(self.position.1, o.position.1);
+        let ex = max(self.position.0 + self.size.0, o.position.0 + o.size.0);
+        let ey = max(self.position.1 + self.size.1, o.position.1 + o.size.1);
+        Self::new(x, y, ex - x, ey - y)
+    }
+}
+
 #[derive(Clone)]
 #[cfg_attr(feature = "debug-derive", derive(Debug))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Window {
     SizeChange(WindowSizeChange),
+    /// Sent when the scale factor of the window changes (e.g. the DPI of the
+    /// screen has been changed), physical size is the same in both aspects.
+    ScaleFactorChanged(WindowSizeChange),
+    /// Repeated redraw requests and damages are merged together and this is
+    /// always delivered after the pending size change.
+    RedrawRequested {
+        damaged_rect: Rect,
+    },
+    /// Position of the window in screen coordinates has been changed.
+    Moved {
+        position: (i64, i64),
+    },
     Focus,
     Defocus,
 }
 
+#[derive(Clone)]
 #[cfg_attr(feature = "debug-derive", derive(Debug))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Move {
     Mouse {
         previous: (i64, i64),
@@ -133,6 +293,9 @@ pub enum Move {
         normalized_current: (f64, f64),
         normalized_delta: (f64, f64),
     },
+    /// Scroll of the mouse wheel or the touchpad in the steps of a usual
+    /// mouse wheel, positive values are to the right and down.
+    Wheel { delta: (f64, f64) },
     Touch {
         index: FingerIndexType,
         previous: (i64, i64),
@@ -141,25 +304,96 @@ pub enum Move {
         normalized_previous: (f64, f64),
         normalized_current: (f64, f64),
         normalized_delta: (f64, f64),
+        pressure: f64,
+        size: f64,
+    },
+    Gamepad {
+        id: GamepadId,
+        axis: GamepadAxis,
+        previous: f64,
+        current: f64,
+        delta: f64,
+    },
+}
+
+/// States of the application on the mobile platforms.
+#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "debug-derive", derive(Debug))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Lifecycle {
+    /// The app is not visible anymore, rendering should be stopped.
+    Suspended,
+    Resumed,
+    /// The app should save its state because it may be killed.
+    SaveState,
+    LowMemory,
+    /// The native window has been created, the rendering surfaces can be
+    /// created again.
+    SurfaceCreated,
+    /// The native window is going to be destroyed, the rendering surfaces
+    /// must be released. In the caller thread dispatch mode, it is delivered
+    /// while the window is still valid.
+    SurfaceDestroyed,
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "debug-derive", derive(Debug))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Gamepad {
+    Connected {
+        id: GamepadId,
+        name: String,
+    },
+    /// Pressed buttons of the gamepad are released before this event.
+    Disconnected {
+        id: GamepadId,
     },
 }
 
+#[derive(Clone)]
 #[cfg_attr(feature = "debug-derive", derive(Debug))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ButtonAction {
     Press,
     Release,
 }
 
+#[derive(Clone)]
 #[cfg_attr(feature = "debug-derive", derive(Debug))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum TouchAction {
     Press,
     HardPress,
     Release,
+    /// The system has taken the touch over, e.g. for its own gesture, the
+    /// finger's gestures are canceled.
+    Cancel,
 }
 
+/// `Tap` and `DoubleTap` are only sent with the `Ended` state.
+#[derive(Clone)]
 #[cfg_attr(feature = "debug-derive", derive(Debug))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum TouchGesture {
-    Tap, // todo
+    Tap {
+        index: FingerIndexType,
+        point: (i64, i64),
+        normalized_point: (f64, f64),
+    },
+    /// Sent after the `Tap` of the second tap.
+    DoubleTap {
+        index: FingerIndexType,
+        point: (i64, i64),
+        normalized_point: (f64, f64),
+    },
+    /// Started when a finger is held without moving, ended on its release.
+    /// The start is detected by the window's `fetch_events`, so it must be
+    /// called regularly while the finger is held.
+    LongPress {
+        index: FingerIndexType,
+        point: (i64, i64),
+        normalized_point: (f64, f64),
+    },
     Drag {
         index: FingerIndexType,
         start: (i64, i64),
@@ -171,6 +405,7 @@ pub enum TouchGesture {
         normalized_current: (f64, f64),
         normalized_delta: (f64, f64),
     },
+    /// Distances are between the two fingers.
     Scale {
         first: (FingerIndexType, TouchState),
         second: (FingerIndexType, TouchState),
@@ -178,14 +413,56 @@ pub enum TouchGesture {
         previous: i64,
         current: i64,
         delta: i64,
-        normalized_start: i64,
-        normalized_previous: i64,
-        normalized_current: i64,
-        normalized_delta: i64,
+        normalized_start: f64,
+        normalized_previous: f64,
+        normalized_current: f64,
+        normalized_delta: f64,
+    },
+    /// Angles are of the vector from the first finger to the second one, in
+    /// radians and clockwise on the screen.
+    Rotate {
+        first: (FingerIndexType, TouchState),
+        second: (FingerIndexType, TouchState),
+        start: f64,
+        previous: f64,
+        current: f64,
+        delta: f64,
     },
 }
 
+/// Thresholds of the gesture recognition, distances are normalized.
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "debug-derive", derive(Debug))]
+pub struct GestureConfig {
+    /// Movements shorter than this are not considered as a drag or a pinch.
+    pub touch_slop: f64,
+    /// Maximum distance between the taps of a double tap.
+    pub double_tap_slop: f64,
+    pub tap_max_duration: Duration,
+    /// Maximum time between the release of the first tap and the press of
+    /// the second one.
+    pub double_tap_interval: Duration,
+    pub long_press_duration: Duration,
+    /// In radians.
+    pub rotation_threshold: f64,
+}
+
+impl Default for GestureConfig {
+    fn default() -> Self {
+        Self {
+            touch_slop: 0.04,
+            double_tap_slop: 0.2,
+            tap_max_duration: Duration::from_millis(300),
+            double_tap_interval: Duration::from_millis(300),
+            long_press_duration: Duration::from_millis(500),
+            rotation_threshold: 0.1,
+        }
+    }
+}
+
+#[derive(Clone)]
 #[cfg_attr(feature = "debug-derive", derive(Debug))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum GestureState {
     Started,
     InMiddle,
@@ -193,23 +470,43 @@ pub enum GestureState {
     Canceled,
 }
 
+#[derive(Clone)]
 #[cfg_attr(feature = "debug-derive", derive(Debug))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Touch {
     Gesture {
+        #[cfg_attr(feature = "serde", serde(with = "serde_instant"))]
         start_time: Instant,
         duration: Duration,
         state: GestureState,
         data: TouchGesture,
     },
+    /// `pressure` is normally in 0..1 but may go above it, `size` is the
+    /// normalized contact area, both are 1 and 0 when the platform can not
+    /// measure them.
     Raw {
         index: FingerIndexType,
         action: TouchAction,
         point: (i64, i64),
         normalized_point: (f64, f64),
+        pressure: f64,
+        size: f64,
     },
 }
 
+#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "debug-derive", derive(Debug))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ActionState {
+    Pressed,
+    Released,
+    /// Value of an axis has been changed.
+    Changed,
+}
+
+#[derive(Clone)]
 #[cfg_attr(feature = "debug-derive", derive(Debug))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Data {
     Move(Move),
     Button {
@@ -217,11 +514,90 @@ pub enum Data {
         action: ButtonAction,
     },
     Touch(Touch),
+    /// Sent for every change of a pen or a stylus. On X11 the pen moves the
+    /// mouse pointer too, so its motions and presses are also delivered as
+    /// the mouse events.
+    Pen {
+        position: (i64, i64),
+        normalized_position: (f64, f64),
+        /// In the range of 0 to 1.
+        pressure: f64,
+        /// Angles of the pen from the normal of the surface in the X and Y
+        /// directions, in degrees between -90 and 90.
+        tilt: (f64, f64),
+        /// Clockwise rotation around the pen's axis, in degrees between 0 and
+        /// 360.
+        rotation: f64,
+        /// The button whose state has been changed by this event.
+        button: Option<(PenButton, ButtonAction)>,
+        /// The eraser end of the pen is in use.
+        eraser: bool,
+        /// False when the pen leaves the detection range of the tablet. X11
+        /// does not report the proximity, so there it is false when the pen
+        /// leaves the window.
+        proximity: bool,
+    },
     Window(Window),
+    Gamepad(Gamepad),
+    Lifecycle(Lifecycle),
+    /// Generated by the `input::ActionMap` for named actions and axes.
+    Action {
+        name: String,
+        state: ActionState,
+        value: f64,
+    },
     Quit,
     Terminate,
 }
 
+impl Data {
+    pub fn category(&self) -> EventCategory {
+        match self {
+            &Data::Move(Move::Mouse { .. }) | &Data::Move(Move::Wheel { .. }) => {
+                EventCategory::MOUSE
+            }
+            &Data::Move(Move::Touch { .. }) | &Data::Touch(_) => EventCategory::TOUCH,
+            &Data::Pen { .. } => EventCategory::PEN,
+            &Data::Button {
+                button: Button::Mouse(_),
+                ..
+            } => EventCategory::MOUSE,
+            &Data::Button {
+                button: Button::Keyboard(_),
+                ..
+            } => EventCategory::KEYBOARD,
+            &Data::Move(Move::Gamepad { .. })
+            | &Data::Button {
+                button: Button::Gamepad { .. },
+                ..
+            }
+            | &Data::Gamepad(_) => EventCategory::GAMEPAD,
+            &Data::Window(_) => EventCategory::WINDOW,
+            &Data::Action { .. } => EventCategory::USER,
+            &Data::Lifecycle(_) | &Data::Quit | &Data::Terminate => EventCategory::LIFECYCLE,
+        }
+    }
+}
+
+bitflags! {
+    /// Interests of a listener, the engine only calls the listener for the
+    /// events of these categories.
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "debug-derive", derive(Debug))]
+    pub struct EventCategory: u8 {
+        const KEYBOARD = 1;
+        const MOUSE = 2;
+        const TOUCH = 4;
+        const WINDOW = 8;
+        const LIFECYCLE = 16;
+        /// Events that are generated on top of the inputs, like the actions.
+        const USER = 32;
+        const GAMEPAD = 64;
+        const PEN = 128;
+    }
+}
+
+#[derive(Clone)]
 #[cfg_attr(feature = "debug-derive", derive(Debug))]
 pub struct Event {
     id: u64,
@@ -261,8 +637,22 @@ pub trait Listener: Send + Sync {
     fn on_event(&mut self, event: &Event) -> bool;
 }
 
-#[derive(Default, Clone, Copy)]
+struct FnListener<F>(F);
+
+impl<F> Listener for FnListener<F>
+where
+    F: FnMut(&Event) -> bool + Send + Sync,
+{
+    fn on_event(&mut self, event: &Event) -> bool {
+        (self.0)(event)
+    }
+}
+
+/// Width and height are in physical pixels, logical sizes are physical sizes
+/// divided by the scale factor.
+#[derive(Clone, Copy)]
 #[cfg_attr(feature = "debug-derive", derive(Debug))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct WindowAspects {
     width: i64,
     height: i64,
@@ -270,250 +660,1153 @@ pub struct WindowAspects {
     ratio: f64,
     normalized_width: f64,
     normalized_height: f64,
+    scale_factor: f64,
+    logical_width: f64,
+    logical_height: f64,
+}
+
+impl WindowAspects {
+    /// An empty window has the zero ratio and normalized sizes, an invalid
+    /// scale factor is replaced by 1.
+    fn new(width: i64, height: i64, scale_factor: f64) -> Self {
+        let smallest = if width > height { height } else { width };
+        let scale_factor = if scale_factor > 0.0 && scale_factor.is_finite() {
+            scale_factor
+        } else {
+            log_e!("Invalid scale factor: {}", scale_factor);
+            1.0
+        };
+        let divide = |a: i64, b: i64| {
+            if smallest > 0 {
+                a as f64 / b as f64
+            } else {
+                0.0
+            }
+        };
+        Self {
+            width,
+            height,
+            smallest,
+            ratio: divide(width, height),
+            normalized_width: divide(width, smallest),
+            normalized_height: divide(height, smallest),
+            scale_factor,
+            logical_width: width as f64 / scale_factor,
+            logical_height: height as f64 / scale_factor,
+        }
+    }
+
+    fn delta(&self, previous: &Self) -> Self {
+        Self {
+            width: self.width - previous.width,
+            height: self.height - previous.height,
+            smallest: self.smallest - previous.smallest,
+            ratio: self.ratio - previous.ratio,
+            normalized_width: self.normalized_width - previous.normalized_width,
+            normalized_height: self.normalized_height - previous.normalized_height,
+            scale_factor: self.scale_factor - previous.scale_factor,
+            logical_width: self.logical_width - previous.logical_width,
+            logical_height: self.logical_height - previous.logical_height,
+        }
+    }
+
+    pub fn get_width(&self) -> i64 {
+        self.width
+    }
+
+    pub fn get_height(&self) -> i64 {
+        self.height
+    }
+
+    pub fn get_smallest(&self) -> i64 {
+        self.smallest
+    }
+
+    pub fn get_ratio(&self) -> f64 {
+        self.ratio
+    }
+
+    pub fn get_normalized_width(&self) -> f64 {
+        self.normalized_width
+    }
+
+    pub fn get_normalized_height(&self) -> f64 {
+        self.normalized_height
+    }
+
+    pub fn get_scale_factor(&self) -> f64 {
+        self.scale_factor
+    }
+
+    pub fn get_logical_width(&self) -> f64 {
+        self.logical_width
+    }
+
+    pub fn get_logical_height(&self) -> f64 {
+        self.logical_height
+    }
+
+    pub fn get_physical_size(&self) -> (i64, i64) {
+        (self.width, self.height)
+    }
+
+    pub fn get_logical_size(&self) -> (f64, f64) {
+        (self.logical_width, self.logical_height)
+    }
+}
+
+impl Default for WindowAspects {
+    fn default() -> Self {
+        Self::new(0, 0, 1.0)
+    }
 }
 
 #[derive(Default)]
 struct WindowState {
     aspects: WindowAspects,
+    position: (i64, i64),
 }
 
 impl WindowState {
+    /// Origin of the normalized coordinates is the center of the window and
+    /// the smallest side of the window is in the [-1, 1] range, every position
+    /// is at the origin in an empty window.
     fn normalize(&self, x: i64, y: i64) -> (f64, f64) {
         (self.normalize_width(x), self.normalize_height(y))
     }
 
     fn normalize_width(&self, x: i64) -> f64 {
-        (x - self.aspects.width) as f64 / self.aspects.smallest as f64
+        self.normalize_length(2 * x - self.aspects.width)
     }
 
     fn normalize_height(&self, x: i64) -> f64 {
-        (x - self.aspects.height) as f64 / self.aspects.smallest as f64
+        self.normalize_length(2 * x - self.aspects.height)
+    }
+
+    fn normalize_length(&self, l: i64) -> f64 {
+        if self.aspects.smallest > 0 {
+            l as f64 / self.aspects.smallest as f64
+        } else {
+            0.0
+        }
     }
 }
 
-#[cfg_attr(feature = "verbose-log", derive(Debug))]
+#[cfg_attr(feature = "debug-derive", derive(Debug))]
 #[derive(Default, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TouchState {
     position: (i64, i64),
     normalized_position: (f64, f64),
+    pressure: f64,
+    size: f64,
     hard_pressed: bool,
 }
 
+impl TouchState {
+    pub(crate) fn new(
+        position: (i64, i64),
+        normalized_position: (f64, f64),
+        pressure: f64,
+        size: f64,
+    ) -> Self {
+        Self {
+            position,
+            normalized_position,
+            pressure,
+            size,
+            hard_pressed: false,
+        }
+    }
+
+    pub fn get_position(&self) -> (i64, i64) {
+        self.position
+    }
+
+    pub fn get_normalized_position(&self) -> (f64, f64) {
+        self.normalized_position
+    }
+
+    pub fn get_pressure(&self) -> f64 {
+        self.pressure
+    }
+
+    pub fn get_size(&self) -> f64 {
+        self.size
+    }
+
+    pub fn is_hard_pressed(&self) -> bool {
+        self.hard_pressed
+    }
+}
+
 #[cfg(all(not(target_os = "android"), not(target_os = "ios")))]
-#[derive(Default)]
+#[derive(Default, Clone)]
 struct PointerState {
     position: (i64, i64),
     normalized_position: (f64, f64),
 }
 
+#[derive(Clone)]
+#[cfg_attr(feature = "debug-derive", derive(Debug))]
+pub struct GamepadState {
+    name: String,
+    axes: BTreeMap<GamepadAxis, f64>,
+}
+
+impl GamepadState {
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_axis(&self, axis: GamepadAxis) -> f64 {
+        self.axes.get(&axis).cloned().unwrap_or(0.0)
+    }
+}
+
 #[derive(Default)]
 struct EngineState {
     window: WindowState,
-    #[cfg(any(target_os = "android", target_os = "ios"))]
+    gamepads: BTreeMap<GamepadId, GamepadState>,
     fingers: BTreeMap<FingerIndexType, TouchState>,
+    gestures: Recognizer,
     #[cfg(all(not(target_os = "android"), not(target_os = "ios")))]
     mouse: PointerState,
     pressed_buttons: BTreeSet<Button>,
+    just_pressed_buttons: BTreeSet<Button>,
+    just_released_buttons: BTreeSet<Button>,
 }
 
-pub struct Engine {
-    listeners: Arc<Mutex<BTreeMap<i64, LinkedList<Weak<RwLock<dyn Listener>>>>>>,
-    processor: Option<JoinHandle<()>>,
-    sender: Sender<Event>,
-    state: Arc<Mutex<EngineState>>,
+/// A snapshot of the input state, the just pressed and just released buttons
+/// are the ones that have been changed since the start of the current frame,
+/// (the last call to the window's `fetch_events`).
+#[derive(Clone)]
+pub struct InputState {
+    pressed_buttons: BTreeSet<Button>,
+    just_pressed_buttons: BTreeSet<Button>,
+    just_released_buttons: BTreeSet<Button>,
+    #[cfg(all(not(target_os = "android"), not(target_os = "ios")))]
+    mouse: PointerState,
+    fingers: BTreeMap<FingerIndexType, TouchState>,
+    gamepads: BTreeMap<GamepadId, GamepadState>,
+    window_aspects: WindowAspects,
 }
 
-impl Engine {
-    pub(crate) fn new() -> Self {
-        let listeners: Arc<Mutex<BTreeMap<i64, LinkedList<Weak<RwLock<dyn Listener>>>>>> =
-            Arc::new(Mutex::new(BTreeMap::new()));
-        let (sender, receiver) = channel();
-        let ls = listeners.clone();
-        let processor = Some(spawn(move || {
-            let mut pending_window_resize: Option<WindowSizeChange> = None;
-            let wait_dur = Duration::from_millis(100);
-            'engine_loop: loop {
-                let e: Option<Event> = match receiver.recv_timeout(wait_dur) {
-                    Ok(e) => Some(e),
-                    Err(_) => None,
-                };
-                let e: Event = if let Some(e) = e {
-                    match e.get_data() {
-                        &Data::Terminate => return,
-                        &Data::Window(Window::SizeChange(ref e)) => {
-                            if pending_window_resize.is_some() {
-                                let p = unwrap_f!(pending_window_resize.as_mut());
-                                p.current = e.current.clone();
-                                p.delta = WindowAspects {
-                                    width: p.current.width - p.previous.width,
-                                    height: p.current.height - p.previous.height,
-                                    ratio: p.current.ratio - p.previous.ratio,
-                                    smallest: p.current.smallest - p.previous.smallest,
-                                    normalized_width: p.current.normalized_width
-                                        - p.previous.normalized_width,
-                                    normalized_height: p.current.normalized_height
-                                        - p.previous.normalized_height,
-                                };
-                            } else {
-                                pending_window_resize = Some(e.clone());
-                            }
-                            continue 'engine_loop;
-                        }
-                        _ => (),
-                    }
-                    e
-                } else if pending_window_resize.is_some() {
-                    let e = Event::new(Data::Window(Window::SizeChange(unwrap_f!(
-                        pending_window_resize.clone()
-                    ))));
-                    pending_window_resize = None;
-                    e
-                } else {
-                    continue 'engine_loop;
-                };
-                let listeners = result_f!(ls.lock());
-                'listeners_loop: for (_, ls) in &*listeners {
-                    for l in ls {
-                        if let Some(l) = l.upgrade() {
-                            if result_f!(l.write()).on_event(&e) {
-                                break 'listeners_loop;
-                            }
-                        }
-                    }
-                }
-            }
-        }));
-        let state = Arc::new(Mutex::new(EngineState::default()));
-        Self {
-            listeners,
-            processor,
-            sender,
-            state,
-        }
+impl InputState {
+    pub fn is_pressed(&self, b: &Button) -> bool {
+        self.pressed_buttons.contains(b)
     }
 
-    pub(crate) fn broadcast(&self, e: Event) {
-        result_f!(self.sender.send(e));
+    pub fn just_pressed(&self, b: &Button) -> bool {
+        self.just_pressed_buttons.contains(b)
     }
 
-    pub fn add(&self, priority: i64, l: Weak<RwLock<dyn Listener>>) {
-        (*result_f!(self.listeners.lock()))
-            .entry(priority)
-            .or_insert(LinkedList::new())
-            .push_back(l);
+    pub fn just_released(&self, b: &Button) -> bool {
+        self.just_released_buttons.contains(b)
     }
 
-    pub fn clean(&self) {
-        let mut listeners = result_f!(self.listeners.lock());
-        for (_, listeners) in &mut *listeners {
-            listeners.drain_filter(|x| x.strong_count() <= 0);
-        }
+    pub fn get_pressed_buttons(&self) -> &BTreeSet<Button> {
+        &self.pressed_buttons
     }
 
-    pub(crate) fn init_window_aspects(&self, width: i64, height: i64) {
-        let smallest = if width > height { height } else { width };
-        let normalized_width = width as f64 / smallest as f64;
-        let normalized_height = height as f64 / smallest as f64;
-        let mut state = result_f!(self.state.lock());
-        state.window.aspects.width = width;
-        state.window.aspects.height = height;
-        state.window.aspects.ratio = width as f64 / height as f64;
-        state.window.aspects.smallest = smallest;
-        state.window.aspects.normalized_width = normalized_width;
-        state.window.aspects.normalized_height = normalized_height;
+    #[cfg(all(not(target_os = "android"), not(target_os = "ios")))]
+    pub fn get_pointer_position(&self) -> (i64, i64) {
+        self.mouse.position
     }
 
     #[cfg(all(not(target_os = "android"), not(target_os = "ios")))]
-    pub(crate) fn init_mouse_position(&self, p: (i64, i64)) {
-        let mut state = result_f!(self.state.lock());
-        state.mouse.position = p;
-        state.mouse.normalized_position = state.window.normalize(p.0, p.1);
+    pub fn get_normalized_pointer_position(&self) -> (f64, f64) {
+        self.mouse.normalized_position
     }
 
-    #[cfg(all(not(target_os = "android"), not(target_os = "ios")))]
-    pub(crate) fn set_mouse_position(&self, cur: (i64, i64)) {
-        self.broadcast(Event::new({
-            let mut state = result_f!(self.state.lock());
-            let nrm_cur = state.window.normalize(cur.0, cur.1);
-            let d = Data::Move(Move::Mouse {
-                previous: state.mouse.position,
-                normalized_previous: state.mouse.normalized_position,
-                current: cur,
-                normalized_current: nrm_cur,
-                delta: (
-                    cur.0 - state.mouse.position.0,
-                    cur.1 - state.mouse.position.1,
-                ),
-                normalized_delta: (
-                    nrm_cur.0 - state.mouse.normalized_position.0,
-                    nrm_cur.1 - state.mouse.normalized_position.1,
-                ),
-            });
-            state.mouse.position = cur;
-            state.mouse.normalized_position = nrm_cur;
-            d
-        }));
+    pub fn get_touches(&self) -> &BTreeMap<FingerIndexType, TouchState> {
+        &self.fingers
     }
 
-    #[cfg(any(target_os = "android", target_os = "ios"))]
-    pub(crate) fn finger_down(&self, x: i64, y: i64, index: FingerIndexType) {
-        let nrm = {
-            let mut s = result_f!(self.state.lock());
-            let nrm = s.window.normalize(x, y);
-            s.fingers.insert(
-                index,
-                TouchState {
-                    position: (x, y),
-                    normalized_position: nrm,
-                    hard_pressed: false,
-                },
-            );
-            nrm
-        };
-        self.broadcast(Event::new(Data::Touch(Touch::Raw {
-            index,
-            action: TouchAction::Press,
-            point: (x, y),
-            normalized_point: nrm,
-        })));
+    /// Returns the connected gamepads.
+    pub fn get_gamepads(&self) -> &BTreeMap<GamepadId, GamepadState> {
+        &self.gamepads
     }
 
-    #[cfg(any(target_os = "android", target_os = "ios"))]
-    pub(crate) fn finger_up(&self, x: i64, y: i64, index: FingerIndexType) {
-        let nrm = {
-            let mut s = result_f!(self.state.lock());
-            s.fingers.remove(&index);
-            s.window.normalize(x, y)
-        };
-        self.broadcast(Event::new(Data::Touch(Touch::Raw {
-            index,
-            action: TouchAction::Release,
-            point: (x, y),
-            normalized_point: nrm,
-        })));
+    pub fn get_window_aspects(&self) -> &WindowAspects {
+        &self.window_aspects
     }
+}
 
-    #[cfg(any(target_os = "android", target_os = "ios"))]
-    pub(crate) fn finger_move(&self, x: i64, y: i64, index: FingerIndexType) {
-        let m = {
-            let mut s = result_f!(self.state.lock());
-            let nrm = s.window.normalize(x, y);
-            let finger = if let Some(finger) = s.fingers.get(&index) {
-                finger.clone()
-            } else {
-                s.fingers.insert(
-                    index,
-                    TouchState {
-                        position: (x, y),
-                        normalized_position: nrm,
-                        hard_pressed: false,
-                    },
-                );
-                return;
-            };
-            let delta = (x - finger.position.0, y - finger.position.1);
-            let normalized_delta = (
-                nrm.0 - finger.normalized_position.0,
+static NEXT_SUBSCRIPTION_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Number of the events that can wait for the dispatch without locking,
+/// newer events go to a slower unbounded queue when it is full.
+pub const EVENT_QUEUE_CAPACITY: usize = 4096;
+
+/// Reading never blocks, writers make a modified copy of the list.
+struct CowList<T> {
+    items: ArcSwap<Vec<T>>,
+    writer: Mutex<()>,
+}
+
+type Receivers = CowList<(u64, Sender<Event>)>;
+
+impl<T: Clone> CowList<T> {
+    fn new() -> Self {
+        Self {
+            items: ArcSwap::from_pointee(Vec::new()),
+            writer: Mutex::new(()),
+        }
+    }
+
+    fn load(&self) -> Arc<Vec<T>> {
+        self.items.load_full()
+    }
+
+    fn update<R, F: FnOnce(&mut Vec<T>) -> R>(&self, f: F) -> R {
+        let _writer = result_f!(self.writer.lock());
+        let mut items = (**self.items.load()).clone();
+        let result = f(&mut items);
+        self.items.store(Arc::new(items));
+        result
+    }
+}
+
+#[derive(Clone)]
+struct ListenerEntry {
+    id: u64,
+    priority: i64,
+    interests: EventCategory,
+    listener: Weak<RwLock<dyn Listener>>,
+}
+
+/// Sorted by the priorities and then by the order of registration.
+type Listeners = CowList<ListenerEntry>;
+
+fn insert_listener(listeners: &mut Vec<ListenerEntry>, entry: ListenerEntry) {
+    let i = listeners
+        .iter()
+        .position(|l| l.priority > entry.priority)
+        .unwrap_or(listeners.len());
+    listeners.insert(i, entry);
+}
+
+/// Keeps a listener registered in the engine, the listener is removed when
+/// the handle is dropped.
+///
+/// An event that is being dispatched at the time of removal may still reach
+/// the listener.
+#[must_use = "the listener is removed as soon as the handle is dropped"]
+pub struct ListenerHandle {
+    id: u64,
+    priority: i64,
+    listeners: Weak<Listeners>,
+    /// Closure listeners are only owned by their handles.
+    owned: Option<Arc<RwLock<dyn Listener>>>,
+}
+
+impl ListenerHandle {
+    pub fn get_priority(&self) -> i64 {
+        self.priority
+    }
+
+    /// The listener goes after the other listeners of the new priority.
+    pub fn set_priority(&mut self, priority: i64) {
+        if priority == self.priority {
+            return;
+        }
+        if let Some(listeners) = self.listeners.upgrade() {
+            let id = self.id;
+            listeners.update(|ls| {
+                if let Some(i) = ls.iter().position(|l| l.id == id) {
+                    let mut entry = ls.remove(i);
+                    entry.priority = priority;
+                    insert_listener(ls, entry);
+                }
+            });
+        }
+        self.priority = priority;
+    }
+
+    pub fn remove(self) {}
+}
+
+impl Drop for ListenerHandle {
+    fn drop(&mut self) {
+        if let Some(listeners) = self.listeners.upgrade() {
+            let id = self.id;
+            listeners.update(|ls| ls.retain(|l| l.id != id));
+        }
+    }
+}
+
+#[cfg(feature = "debug-derive")]
+impl std::fmt::Debug for ListenerHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "ListenerHandle({}, priority: {})",
+            self.id, self.priority
+        )
+    }
+}
+
+/// Specifies the thread that listeners receive events on.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "debug-derive", derive(Debug))]
+pub enum DispatchMode {
+    /// Events are dispatched on the engine's own thread as soon as they
+    /// arrive, this is the default mode except on the web.
+    Threaded,
+    /// Events are queued and dispatched on the caller's thread when the
+    /// window's `fetch_events` (or `Engine::dispatch`) is called.
+    CallerThread,
+}
+
+/// Pull-style alternative to the `Listener`, it receives every event in order
+/// and independently of the listeners' chain.
+pub struct EventReceiver {
+    receiver: Receiver<Event>,
+}
+
+impl EventReceiver {
+    /// Returns the received events without blocking.
+    pub fn drain<'a>(&'a self) -> TryIter<'a, Event> {
+        self.receiver.try_iter()
+    }
+
+    pub fn try_recv(&self) -> Option<Event> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+impl<'a> IntoIterator for &'a EventReceiver {
+    type Item = Event;
+    type IntoIter = TryIter<'a, Event>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.drain()
+    }
+}
+
+/// Policy of merging the consecutive window size changes, a pending size
+/// change is always dispatched before any later event except the redraws.
+/// In the caller thread mode, the pending size change is also dispatched at
+/// the end of each dispatch.
+#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "debug-derive", derive(Debug))]
+pub enum ResizeCoalescing {
+    /// Every size change is dispatched as soon as it arrives.
+    Immediate,
+    /// Size change is dispatched when no other size change arrives for the
+    /// duration, this is the default with 100 milliseconds.
+    Debounce(Duration),
+    /// Same as the debounce but the size change is not held for more than the
+    /// `max_latency` after the first merged size change.
+    MaxLatency {
+        debounce: Duration,
+        max_latency: Duration,
+    },
+}
+
+impl Default for ResizeCoalescing {
+    fn default() -> Self {
+        ResizeCoalescing::Debounce(Duration::from_millis(100))
+    }
+}
+
+enum Item {
+    Event(Box<Event>),
+    /// Signaled when the processor reaches it, every event before it has been
+    /// dispatched by then.
+    #[cfg(any(target_os = "android", test))]
+    Ack(Sender<()>),
+}
+
+/// Lock-free queue of the events that wait for the dispatch, no event is
+/// dropped when it is full.
+struct Queue {
+    events: ArrayQueue<Item>,
+    /// Newer items than the ones in `events`, it is used while not empty.
+    overflow: Mutex<VecDeque<Item>>,
+    is_overflowed: AtomicBool,
+    /// The engine's thread that must be woken up on new events.
+    consumer: ArcSwapOption<Thread>,
+    terminate: AtomicBool,
+}
+
+impl Queue {
+    fn push(&self, e: Event) {
+        self.push_item(Item::Event(Box::new(e)));
+    }
+
+    fn push_item(&self, i: Item) {
+        let i = if self.is_overflowed.load(Ordering::Acquire) {
+            Some(i)
+        } else {
+            self.events.push(i).err()
+        };
+        if let Some(i) = i {
+            let mut overflow = result_f!(self.overflow.lock());
+            if overflow.is_empty() {
+                log_e!("Event queue is full, the events wait in the overflow.");
+            }
+            overflow.push_back(i);
+            self.is_overflowed.store(true, Ordering::Release);
+        }
+        if let Some(ref t) = *self.consumer.load() {
+            t.unpark();
+        }
+    }
+
+    fn pop(&self) -> Option<Item> {
+        if let Some(e) = self.events.pop() {
+            return Some(e);
+        }
+        if !self.is_overflowed.load(Ordering::Acquire) {
+            return None;
+        }
+        let mut overflow = result_f!(self.overflow.lock());
+        let e = overflow.pop_front();
+        if overflow.is_empty() {
+            self.is_overflowed.store(false, Ordering::Release);
+        }
+        e
+    }
+
+    /// Wakeups can be spurious, so it may return sooner without any event.
+    fn pop_timeout(&self, timeout: Duration) -> Option<Item> {
+        if let Some(e) = self.pop() {
+            return Some(e);
+        }
+        if timeout > Duration::from_millis(0) {
+            park_timeout(timeout);
+        }
+        self.pop()
+    }
+}
+
+/// Sends events into the engine's queue from anywhere.
+#[derive(Clone)]
+pub(crate) struct EventSender {
+    queue: Arc<Queue>,
+}
+
+impl EventSender {
+    pub(crate) fn send(&self, e: Event) {
+        self.queue.push(e);
+    }
+}
+
+struct PendingWindowResize {
+    change: WindowSizeChange,
+    first: Instant,
+    last: Instant,
+}
+
+struct Processor {
+    queue: Arc<Queue>,
+    listeners: Arc<Listeners>,
+    receivers: Arc<Receivers>,
+    redraw_requested: Arc<AtomicBool>,
+    resize_coalescing: Arc<ArcSwap<ResizeCoalescing>>,
+    pending_window_resize: Option<PendingWindowResize>,
+    pending_redraw: Option<Rect>,
+    wait_dur: Duration,
+}
+
+impl Processor {
+    /// Processes at most one received event, returns false when there is no
+    /// need to continue, blocking steps only stop on termination.
+    fn step(&mut self, blocking: bool) -> bool {
+        if self.queue.terminate.load(Ordering::Acquire) {
+            return false;
+        }
+        let policy = **self.resize_coalescing.load();
+        let deadline = self.pending_window_resize.as_ref().map(|p| match policy {
+            ResizeCoalescing::Immediate => p.last,
+            ResizeCoalescing::Debounce(d) => p.last + d,
+            ResizeCoalescing::MaxLatency {
+                debounce,
+                max_latency,
+            } => min(p.last + debounce, p.first + max_latency),
+        });
+        // A pending redraw waits for the pending size change, otherwise it is
+        // delivered as soon as the queue is empty.
+        let e: Option<Item> = if blocking {
+            let now = Instant::now();
+            self.queue.pop_timeout(match deadline {
+                Some(d) if d > now => d - now,
+                Some(_) => Duration::from_millis(0),
+                None if self.pending_redraw.is_some() => Duration::from_millis(0),
+                None => self.wait_dur,
+            })
+        } else {
+            self.queue.pop()
+        };
+        let e = match e {
+            Some(Item::Event(e)) => *e,
+            #[cfg(any(target_os = "android", test))]
+            Some(Item::Ack(s)) => {
+                let _ = s.send(());
+                return true;
+            }
+            None => {
+                if self.pending_window_resize.is_some() {
+                    if !blocking || deadline.map(|d| d <= Instant::now()).unwrap_or(true) {
+                        self.flush_window_resize();
+                    }
+                } else if let Some(damaged_rect) = self.pending_redraw.take() {
+                    self.redraw_requested.store(false, Ordering::Relaxed);
+                    self.dispatch(&Event::new(Data::Window(Window::RedrawRequested {
+                        damaged_rect,
+                    })));
+                } else {
+                    return blocking;
+                }
+                return true;
+            }
+        };
+        match e.get_data() {
+            Data::Terminate => return false,
+            Data::Window(Window::SizeChange(c)) => {
+                let now = Instant::now();
+                if let Some(ref mut p) = self.pending_window_resize {
+                    p.change.current = c.current;
+                    p.change.delta = p.change.current.delta(&p.change.previous);
+                    p.last = now;
+                } else {
+                    self.pending_window_resize = Some(PendingWindowResize {
+                        change: *c,
+                        first: now,
+                        last: now,
+                    });
+                }
+                if policy == ResizeCoalescing::Immediate {
+                    self.flush_window_resize();
+                }
+            }
+            Data::Window(Window::RedrawRequested { damaged_rect }) => {
+                self.pending_redraw = Some(match self.pending_redraw {
+                    Some(r) => r.union(damaged_rect),
+                    None => *damaged_rect,
+                });
+            }
+            _ => {
+                self.flush_window_resize();
+                self.dispatch(&e);
+            }
+        }
+        true
+    }
+
+    /// Dispatches the pending size change, it also makes the pending redraw
+    /// to cover the whole window.
+    fn flush_window_resize(&mut self) {
+        if let Some(p) = self.pending_window_resize.take() {
+            let c = p.change;
+            if let Some(r) = self.pending_redraw {
+                self.pending_redraw =
+                    Some(r.union(&Rect::new(0, 0, c.current.width, c.current.height)));
+            }
+            self.dispatch(&Event::new(Data::Window(Window::SizeChange(c))));
+        }
+    }
+
+    fn dispatch(&self, e: &Event) {
+        let receivers = self.receivers.load();
+        let disconnected: Vec<u64> = receivers
+            .iter()
+            .filter(|(_, r)| r.send(e.clone()).is_err())
+            .map(|&(id, _)| id)
+            .collect();
+        if !disconnected.is_empty() {
+            self.receivers
+                .update(|rs| rs.retain(|&(id, _)| !disconnected.contains(&id)));
+        }
+        // Listeners are called on a snapshot of the list, so they can add or
+        // remove listeners, the dead ones are cleaned on the way.
+        let category = e.get_data().category();
+        let mut has_dead = false;
+        for l in self.listeners.load().iter() {
+            if !l.interests.intersects(category) {
+                has_dead |= l.listener.strong_count() == 0;
+                continue;
+            }
+            match l.listener.upgrade() {
+                Some(l) => {
+                    if result_f!(l.write()).on_event(e) {
+                        break;
+                    }
+                }
+                None => has_dead = true,
+            }
+        }
+        if has_dead {
+            self.listeners
+                .update(|ls| ls.retain(|l| l.listener.strong_count() > 0));
+        }
+    }
+}
+
+pub struct Engine {
+    listeners: Arc<Listeners>,
+    receivers: Arc<Receivers>,
+    default_receiver: Mutex<Option<EventReceiver>>,
+    processor: Arc<Mutex<Processor>>,
+    processor_thread: Mutex<Option<JoinHandle<()>>>,
+    queue: Arc<Queue>,
+    state: Arc<Mutex<EngineState>>,
+    redraw_requested: Arc<AtomicBool>,
+    resize_coalescing: Arc<ArcSwap<ResizeCoalescing>>,
+}
+
+impl Engine {
+    pub(crate) fn new() -> Self {
+        let listeners = Arc::new(Listeners::new());
+        let receivers = Arc::new(CowList::new());
+        let queue = Arc::new(Queue {
+            events: ArrayQueue::new(EVENT_QUEUE_CAPACITY),
+            overflow: Mutex::new(VecDeque::new()),
+            is_overflowed: AtomicBool::new(false),
+            consumer: ArcSwapOption::empty(),
+            terminate: AtomicBool::new(false),
+        });
+        let redraw_requested = Arc::new(AtomicBool::new(false));
+        let resize_coalescing = Arc::new(ArcSwap::from_pointee(ResizeCoalescing::default()));
+        let processor = Arc::new(Mutex::new(Processor {
+            queue: queue.clone(),
+            listeners: listeners.clone(),
+            receivers: receivers.clone(),
+            redraw_requested: redraw_requested.clone(),
+            resize_coalescing: resize_coalescing.clone(),
+            pending_window_resize: None,
+            pending_redraw: None,
+            wait_dur: Duration::from_millis(100),
+        }));
+        let state = Arc::new(Mutex::new(EngineState::default()));
+        let result = Self {
+            listeners,
+            receivers,
+            default_receiver: Mutex::new(None),
+            processor,
+            processor_thread: Mutex::new(None),
+            queue,
+            state,
+            redraw_requested,
+            resize_coalescing,
+        };
+        // The browsers can not spawn threads.
+        #[cfg(not(target_arch = "wasm32"))]
+        result.set_dispatch_mode(DispatchMode::Threaded);
+        result
+    }
+
+    /// Switching from the threaded mode waits for the engine's thread to
+    /// finish, so it must not be called from inside a listener.
+    pub fn set_dispatch_mode(&self, mode: DispatchMode) {
+        let mut thread = result_f!(self.processor_thread.lock());
+        match mode {
+            DispatchMode::Threaded => {
+                if thread.is_some() {
+                    return;
+                }
+                if cfg!(target_arch = "wasm32") {
+                    log_e!("Threaded dispatch mode is not supported on the web.");
+                    return;
+                }
+                let processor = self.processor.clone();
+                let t = spawn(move || {
+                    let mut processor = result_f!(processor.lock());
+                    while processor.step(true) {}
+                });
+                self.queue
+                    .consumer
+                    .store(Some(Arc::new(t.thread().clone())));
+                *thread = Some(t);
+            }
+            DispatchMode::CallerThread => {
+                if let Some(t) = thread.take() {
+                    self.stop_processor_thread(t);
+                    self.queue.terminate.store(false, Ordering::Release);
+                }
+            }
+        }
+    }
+
+    fn stop_processor_thread(&self, t: JoinHandle<()>) {
+        self.queue.terminate.store(true, Ordering::Release);
+        t.thread().unpark();
+        self.queue.consumer.store(None);
+        if t.thread().id() == thread::current().id() {
+            log_e!("The engine's thread can not stop itself.");
+        } else {
+            result_f!(t.join());
+        }
+    }
+
+    pub fn set_resize_coalescing(&self, c: ResizeCoalescing) {
+        self.resize_coalescing.store(Arc::new(c));
+    }
+
+    pub fn get_resize_coalescing(&self) -> ResizeCoalescing {
+        **self.resize_coalescing.load()
+    }
+
+    pub fn get_dispatch_mode(&self) -> DispatchMode {
+        if result_f!(self.processor_thread.lock()).is_some() {
+            DispatchMode::Threaded
+        } else {
+            DispatchMode::CallerThread
+        }
+    }
+
+    /// In the caller thread mode, dispatches all of the queued events to the
+    /// listeners, in the threaded mode it does nothing.
+    pub fn dispatch(&self) {
+        if self.get_dispatch_mode() == DispatchMode::Threaded {
+            return;
+        }
+        // A listener calling this again must not dead-lock.
+        if let Ok(mut processor) = self.processor.try_lock() {
+            while processor.step(false) {}
+        }
+    }
+
+    pub(crate) fn broadcast(&self, e: Event) {
+        self.queue.push(e);
+    }
+
+    /// Returns after the listeners have received the event in both of the
+    /// dispatch modes, it is for the events that the platform acts on as soon
+    /// as their handling returns.
+    #[cfg(any(target_os = "android", test))]
+    fn broadcast_and_wait(&self, e: Event) {
+        self.broadcast(e);
+        {
+            let thread = result_f!(self.processor_thread.lock());
+            match *thread {
+                Some(ref t) if t.thread().id() == thread::current().id() => {
+                    log_e!("Events can not be waited for inside a listener.");
+                    return;
+                }
+                Some(_) => (),
+                None => {
+                    drop(thread);
+                    self.dispatch();
+                    return;
+                }
+            }
+        }
+        let (sender, receiver) = channel();
+        self.queue.push_item(Item::Ack(sender));
+        let _ = receiver.recv();
+    }
+
+    pub(crate) fn get_sender(&self) -> EventSender {
+        EventSender {
+            queue: self.queue.clone(),
+        }
+    }
+
+    /// Listeners with smaller priorities receive the events sooner, only the
+    /// events of the `interests` reach the listener. The listener is removed
+    /// when it is dropped or when the returned handle is dropped.
+    pub fn add(
+        &self,
+        priority: i64,
+        interests: EventCategory,
+        l: Weak<RwLock<dyn Listener>>,
+    ) -> ListenerHandle {
+        let id = NEXT_SUBSCRIPTION_ID.fetch_add(1, Ordering::Relaxed);
+        self.listeners.update(|ls| {
+            insert_listener(
+                ls,
+                ListenerEntry {
+                    id,
+                    priority,
+                    interests,
+                    listener: l,
+                },
+            )
+        });
+        ListenerHandle {
+            id,
+            priority,
+            listeners: Arc::downgrade(&self.listeners),
+            owned: None,
+        }
+    }
+
+    /// Registers the closure as a listener of all of the events, the closure
+    /// lives as long as the returned handle.
+    pub fn on<F>(&self, priority: i64, f: F) -> ListenerHandle
+    where
+        F: FnMut(&Event) -> bool + Send + Sync + 'static,
+    {
+        self.on_category(priority, EventCategory::all(), f)
+    }
+
+    pub fn on_category<F>(&self, priority: i64, interests: EventCategory, f: F) -> ListenerHandle
+    where
+        F: FnMut(&Event) -> bool + Send + Sync + 'static,
+    {
+        let l: Arc<RwLock<dyn Listener>> = Arc::new(RwLock::new(FnListener(f)));
+        let mut result = self.add(priority, interests, Arc::downgrade(&l));
+        result.owned = Some(l);
+        result
+    }
+
+    /// The closure receives the press and release of the `key`.
+    pub fn on_key<F>(&self, priority: i64, key: Keyboard, mut f: F) -> ListenerHandle
+    where
+        F: FnMut(&ButtonAction) -> bool + Send + Sync + 'static,
+    {
+        self.on_category(priority, EventCategory::KEYBOARD, move |e| {
+            match e.get_data() {
+                &Data::Button {
+                    button: Button::Keyboard(ref k),
+                    ref action,
+                } if *k == key => f(action),
+                _ => false,
+            }
+        })
+    }
+
+    /// The closure receives the coalesced size changes of the window.
+    pub fn on_resize<F>(&self, priority: i64, mut f: F) -> ListenerHandle
+    where
+        F: FnMut(&WindowSizeChange) -> bool + Send + Sync + 'static,
+    {
+        self.on_category(priority, EventCategory::WINDOW, move |e| {
+            match e.get_data() {
+                &Data::Window(Window::SizeChange(ref c)) => f(c),
+                _ => false,
+            }
+        })
+    }
+
+    /// Returns a receiver that gets every event from now on, events that are
+    /// not drained stay in memory as long as the receiver is alive.
+    pub fn subscribe(&self) -> EventReceiver {
+        let (sender, receiver) = channel();
+        let id = NEXT_SUBSCRIPTION_ID.fetch_add(1, Ordering::Relaxed);
+        self.receivers.update(|rs| rs.push((id, sender)));
+        EventReceiver { receiver }
+    }
+
+    /// Returns the events that have been dispatched since the previous call,
+    /// the engine only keeps the events after the first call. The first call
+    /// also returns the queued events that it dispatches.
+    pub fn drain(&self) -> IntoIter<Event> {
+        {
+            let mut receiver = result_f!(self.default_receiver.lock());
+            if receiver.is_none() {
+                *receiver = Some(self.subscribe());
+            }
+        }
+        self.dispatch();
+        unwrap_f!(result_f!(self.default_receiver.lock()).as_ref())
+            .drain()
+            .collect::<Vec<Event>>()
+            .into_iter()
+    }
+
+    /// Dead listeners are also cleaned during each dispatch, this is only
+    /// needed for releasing them sooner.
+    pub fn clean(&self) {
+        self.listeners
+            .update(|ls| ls.retain(|l| l.listener.strong_count() > 0));
+    }
+
+    pub(crate) fn init_window_aspects(&self, width: i64, height: i64, scale_factor: f64) {
+        result_f!(self.state.lock()).window.aspects =
+            WindowAspects::new(width, height, scale_factor);
+    }
+
+    pub fn get_window_aspects(&self) -> WindowAspects {
+        result_f!(self.state.lock()).window.aspects
+    }
+
+    pub fn input_state(&self) -> InputState {
+        let state = result_f!(self.state.lock());
+        InputState {
+            pressed_buttons: state.pressed_buttons.clone(),
+            just_pressed_buttons: state.just_pressed_buttons.clone(),
+            just_released_buttons: state.just_released_buttons.clone(),
+            #[cfg(all(not(target_os = "android"), not(target_os = "ios")))]
+            mouse: state.mouse.clone(),
+            fingers: state.fingers.clone(),
+            gamepads: state.gamepads.clone(),
+            window_aspects: state.window.aspects,
+        }
+    }
+
+    /// Called by the window at the start of each `fetch_events`.
+    pub(crate) fn start_frame(&self) {
+        let gestures = {
+            let mut state = result_f!(self.state.lock());
+            state.just_pressed_buttons.clear();
+            state.just_released_buttons.clear();
+            state.gestures.tick(Instant::now())
+        };
+        self.broadcast_gestures(gestures);
+    }
+
+    /// Android windows always fill the screen.
+    #[cfg(any(not(target_os = "android"), feature = "headless"))]
+    pub(crate) fn init_window_position(&self, position: (i64, i64)) {
+        result_f!(self.state.lock()).window.position = position;
+    }
+
+    pub fn get_window_position(&self) -> (i64, i64) {
+        result_f!(self.state.lock()).window.position
+    }
+
+    #[cfg(all(not(target_os = "android"), not(target_os = "ios")))]
+    pub(crate) fn init_mouse_position(&self, p: (i64, i64)) {
+        let mut state = result_f!(self.state.lock());
+        state.mouse.position = p;
+        state.mouse.normalized_position = state.window.normalize(p.0, p.1);
+    }
+
+    #[cfg(all(not(target_os = "android"), not(target_os = "ios")))]
+    pub(crate) fn set_mouse_position(&self, cur: (i64, i64)) {
+        self.broadcast(Event::new({
+            let mut state = result_f!(self.state.lock());
+            let nrm_cur = state.window.normalize(cur.0, cur.1);
+            let d = Data::Move(Move::Mouse {
+                previous: state.mouse.position,
+                normalized_previous: state.mouse.normalized_position,
+                current: cur,
+                normalized_current: nrm_cur,
+                delta: (
+                    cur.0 - state.mouse.position.0,
+                    cur.1 - state.mouse.position.1,
+                ),
+                normalized_delta: (
+                    nrm_cur.0 - state.mouse.normalized_position.0,
+                    nrm_cur.1 - state.mouse.normalized_position.1,
+                ),
+            });
+            state.mouse.position = cur;
+            state.mouse.normalized_position = nrm_cur;
+            d
+        }));
+    }
+
+    #[cfg(all(not(target_os = "android"), not(target_os = "ios")))]
+    pub(crate) fn wheel_scrolled(&self, delta: (f64, f64)) {
+        self.broadcast(Event::new(Data::Move(Move::Wheel { delta })));
+    }
+
+    pub(crate) fn finger_down(
+        &self,
+        x: i64,
+        y: i64,
+        index: FingerIndexType,
+        pressure: f64,
+        size: f64,
+    ) {
+        let (nrm, gestures) = {
+            let mut s = result_f!(self.state.lock());
+            let nrm = s.window.normalize(x, y);
+            s.fingers
+                .insert(index, TouchState::new((x, y), nrm, pressure, size));
+            let s = &mut *s;
+            let gestures = s.gestures.finger_down(index, &s.fingers, Instant::now());
+            (nrm, gestures)
+        };
+        self.broadcast(Event::new(Data::Touch(Touch::Raw {
+            index,
+            action: TouchAction::Press,
+            point: (x, y),
+            normalized_point: nrm,
+            pressure,
+            size,
+        })));
+        self.broadcast_gestures(gestures);
+    }
+
+    pub(crate) fn finger_up(
+        &self,
+        x: i64,
+        y: i64,
+        index: FingerIndexType,
+        pressure: f64,
+        size: f64,
+    ) {
+        self.finger_lifted(x, y, index, pressure, size, TouchAction::Release);
+    }
+
+    pub(crate) fn finger_cancel(
+        &self,
+        x: i64,
+        y: i64,
+        index: FingerIndexType,
+        pressure: f64,
+        size: f64,
+    ) {
+        self.finger_lifted(x, y, index, pressure, size, TouchAction::Cancel);
+    }
+
+    fn finger_lifted(
+        &self,
+        x: i64,
+        y: i64,
+        index: FingerIndexType,
+        pressure: f64,
+        size: f64,
+        action: TouchAction,
+    ) {
+        let (nrm, gestures) = {
+            let mut s = result_f!(self.state.lock());
+            s.fingers.remove(&index);
+            let nrm = s.window.normalize(x, y);
+            let touch = TouchState::new((x, y), nrm, pressure, size);
+            let now = Instant::now();
+            let gestures = match action {
+                TouchAction::Cancel => s.gestures.finger_cancel(index, &touch, now),
+                _ => s.gestures.finger_up(index, &touch, now),
+            };
+            (nrm, gestures)
+        };
+        self.broadcast(Event::new(Data::Touch(Touch::Raw {
+            index,
+            action,
+            point: (x, y),
+            normalized_point: nrm,
+            pressure,
+            size,
+        })));
+        self.broadcast_gestures(gestures);
+    }
+
+    /// Moves that change nothing are dropped, platforms like Android report
+    /// every finger when only one of them has moved.
+    pub(crate) fn finger_move(
+        &self,
+        x: i64,
+        y: i64,
+        index: FingerIndexType,
+        pressure: f64,
+        size: f64,
+    ) {
+        let (m, gestures) = {
+            let mut s = result_f!(self.state.lock());
+            let nrm = s.window.normalize(x, y);
+            let touch = TouchState::new((x, y), nrm, pressure, size);
+            let finger = if let Some(finger) = s.fingers.get(&index) {
+                finger.clone()
+            } else {
+                s.fingers.insert(index, touch);
+                return;
+            };
+            if finger.position == touch.position
+                && finger.pressure == touch.pressure
+                && finger.size == touch.size
+            {
+                return;
+            }
+            let delta = (x - finger.position.0, y - finger.position.1);
+            let normalized_delta = (
+                nrm.0 - finger.normalized_position.0,
                 nrm.1 - finger.normalized_position.1,
             );
             let m = Move::Touch {
@@ -524,24 +1817,37 @@ impl Engine {
                 normalized_previous: finger.normalized_position,
                 normalized_current: nrm,
                 normalized_delta,
+                pressure,
+                size,
             };
-            s.fingers.insert(
-                index,
-                TouchState {
-                    position: (x, y),
-                    normalized_position: nrm,
-                    hard_pressed: false,
-                },
-            );
-            m
+            let gestures = s.gestures.finger_move(index, &touch, Instant::now());
+            s.fingers.insert(index, touch);
+            (m, gestures)
         };
         self.broadcast(Event::new(Data::Move(m)));
+        self.broadcast_gestures(gestures);
+    }
+
+    fn broadcast_gestures(&self, gestures: Vec<Touch>) {
+        for g in gestures {
+            self.broadcast(Event::new(Data::Touch(g)));
+        }
+    }
+
+    pub fn get_gesture_config(&self) -> GestureConfig {
+        result_f!(self.state.lock()).gestures.get_config()
+    }
+
+    pub fn set_gesture_config(&self, config: GestureConfig) {
+        result_f!(self.state.lock()).gestures.set_config(config);
     }
 
     pub(crate) fn button_pressed(&self, b: Button) {
         self.broadcast(Event::new({
             let mut state = result_f!(self.state.lock());
-            state.pressed_buttons.insert(b.clone());
+            if state.pressed_buttons.insert(b.clone()) {
+                state.just_pressed_buttons.insert(b.clone());
+            }
             Data::Button {
                 button: b,
                 action: ButtonAction::Press,
@@ -552,7 +1858,9 @@ impl Engine {
     pub(crate) fn button_released(&self, b: Button) {
         self.broadcast(Event::new({
             let mut state = result_f!(self.state.lock());
-            state.pressed_buttons.remove(&b);
+            if state.pressed_buttons.remove(&b) {
+                state.just_released_buttons.insert(b.clone());
+            }
             Data::Button {
                 button: b,
                 action: ButtonAction::Release,
@@ -560,6 +1868,91 @@ impl Engine {
         }));
     }
 
+    /// Presses or releases the button only if its state is different, it is
+    /// for the platforms that report the modifiers' states with the keys.
+    #[cfg(target_os = "android")]
+    pub(crate) fn sync_button(&self, b: Button, pressed: bool) {
+        if result_f!(self.state.lock()).pressed_buttons.contains(&b) == pressed {
+            return;
+        }
+        if pressed {
+            self.button_pressed(b);
+        } else {
+            self.button_released(b);
+        }
+    }
+
+    pub(crate) fn gamepad_connected(&self, id: GamepadId, name: String) {
+        result_f!(self.state.lock()).gamepads.insert(
+            id,
+            GamepadState {
+                name: name.clone(),
+                axes: BTreeMap::new(),
+            },
+        );
+        self.broadcast(Event::new(Data::Gamepad(Gamepad::Connected { id, name })));
+    }
+
+    pub(crate) fn gamepad_disconnected(&self, id: GamepadId) {
+        let pressed: Vec<Button> = {
+            let mut state = result_f!(self.state.lock());
+            if state.gamepads.remove(&id).is_none() {
+                return;
+            }
+            state
+                .pressed_buttons
+                .iter()
+                .filter(|b| match b {
+                    &&Button::Gamepad { id: i, .. } => i == id,
+                    _ => false,
+                })
+                .cloned()
+                .collect()
+        };
+        for b in pressed {
+            self.button_released(b);
+        }
+        self.broadcast(Event::new(Data::Gamepad(Gamepad::Disconnected { id })));
+    }
+
+    pub(crate) fn gamepad_axis_changed(&self, id: GamepadId, axis: GamepadAxis, value: f64) {
+        let data = {
+            let mut state = result_f!(self.state.lock());
+            let gamepad = match state.gamepads.get_mut(&id) {
+                Some(g) => g,
+                None => return,
+            };
+            let previous = gamepad.axes.insert(axis, value).unwrap_or(0.0);
+            if previous == value {
+                return;
+            }
+            Data::Move(Move::Gamepad {
+                id,
+                axis,
+                previous,
+                current: value,
+                delta: value - previous,
+            })
+        };
+        self.broadcast(Event::new(data));
+    }
+
+    pub(crate) fn pen_changed(&self, pen: PenState) {
+        let normalized_position = result_f!(self.state.lock())
+            .window
+            .normalize(pen.position.0, pen.position.1);
+        self.broadcast(Event::new(Data::Pen {
+            position: pen.position,
+            normalized_position,
+            pressure: pen.pressure,
+            tilt: pen.tilt,
+            rotation: pen.rotation,
+            button: pen.button,
+            eraser: pen.eraser,
+            proximity: pen.proximity,
+        }));
+    }
+
     pub(crate) fn window_size_changed(&self, width: i64, height: i64) {
         if width <= 0 || height <= 0 {
             return;
@@ -569,50 +1962,169 @@ impl Engine {
             if width == state.window.aspects.width && height == state.window.aspects.height {
                 return;
             }
-            let ratio = width as f64 / height as f64;
-            let smallest = if width > height { height } else { width };
-            let normalized_width = width as f64 / smallest as f64;
-            let normalized_height = height as f64 / smallest as f64;
-            let d = Window::SizeChange(WindowSizeChange {
-                current: WindowAspects {
-                    width,
-                    height,
-                    ratio,
-                    smallest,
-                    normalized_width,
-                    normalized_height,
-                },
-                previous: WindowAspects {
-                    width: state.window.aspects.width,
-                    height: state.window.aspects.height,
-                    ratio: state.window.aspects.ratio,
-                    smallest: state.window.aspects.smallest,
-                    normalized_width: state.window.aspects.normalized_width,
-                    normalized_height: state.window.aspects.normalized_height,
-                },
-                delta: WindowAspects {
-                    width: width - state.window.aspects.width,
-                    height: height - state.window.aspects.height,
-                    ratio: ratio - state.window.aspects.ratio,
-                    smallest: smallest - state.window.aspects.smallest,
-                    normalized_width: normalized_width - state.window.aspects.normalized_width,
-                    normalized_height: normalized_height - state.window.aspects.normalized_height,
-                },
-            });
-            state.window.aspects.width = width;
-            state.window.aspects.height = height;
-            state.window.aspects.ratio = ratio;
-            state.window.aspects.smallest = smallest;
-            state.window.aspects.normalized_width = normalized_width;
-            state.window.aspects.normalized_height = normalized_height;
-            d
+            let previous = state.window.aspects;
+            let current = WindowAspects::new(width, height, previous.scale_factor);
+            state.window.aspects = current;
+            Window::SizeChange(WindowSizeChange {
+                current,
+                previous,
+                delta: current.delta(&previous),
+            })
         })));
     }
 
+    pub(crate) fn window_moved(&self, position: (i64, i64)) {
+        {
+            let mut state = result_f!(self.state.lock());
+            if position == state.window.position {
+                return;
+            }
+            state.window.position = position;
+        }
+        self.broadcast(Event::new(Data::Window(Window::Moved { position })));
+    }
+
+    pub(crate) fn scale_factor_changed(&self, scale_factor: f64) {
+        if !(scale_factor > 0.0 && scale_factor.is_finite()) {
+            return;
+        }
+        self.broadcast(Event::new(Data::Window({
+            let mut state = result_f!(self.state.lock());
+            let previous = state.window.aspects;
+            if (scale_factor - previous.scale_factor).abs() < 1e-6 {
+                return;
+            }
+            let current = WindowAspects::new(previous.width, previous.height, scale_factor);
+            state.window.aspects = current;
+            Window::ScaleFactorChanged(WindowSizeChange {
+                current,
+                previous,
+                delta: current.delta(&previous),
+            })
+        })));
+    }
+
+    /// Requests a `RedrawRequested` for the whole window, requests that are
+    /// made before the delivery of the previous one are ignored.
+    pub fn request_redraw(&self) {
+        if self.redraw_requested.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        let aspects = self.get_window_aspects();
+        self.broadcast(Event::new(Data::Window(Window::RedrawRequested {
+            damaged_rect: Rect::new(0, 0, aspects.width, aspects.height),
+        })));
+    }
+
+    pub(crate) fn window_damaged(&self, damaged_rect: Rect) {
+        self.broadcast(Event::new(Data::Window(Window::RedrawRequested {
+            damaged_rect,
+        })));
+    }
+
+    /// Feeds the data into the engine as if it has been received from the
+    /// platform, the state of the engine is updated for the tracked inputs.
+    pub fn inject(&self, data: Data) {
+        match data {
+            Data::Button {
+                button,
+                action: ButtonAction::Press,
+            } => self.button_pressed(button),
+            Data::Button {
+                button,
+                action: ButtonAction::Release,
+            } => self.button_released(button),
+            #[cfg(all(not(target_os = "android"), not(target_os = "ios")))]
+            Data::Move(Move::Mouse { current, .. }) => self.set_mouse_position(current),
+            Data::Move(Move::Touch {
+                index,
+                current,
+                pressure,
+                size,
+                ..
+            }) => self.finger_move(current.0, current.1, index, pressure, size),
+            Data::Touch(Touch::Raw {
+                index,
+                action: TouchAction::Press,
+                point,
+                pressure,
+                size,
+                ..
+            }) => self.finger_down(point.0, point.1, index, pressure, size),
+            Data::Touch(Touch::Raw {
+                index,
+                action: TouchAction::Release,
+                point,
+                pressure,
+                size,
+                ..
+            }) => self.finger_up(point.0, point.1, index, pressure, size),
+            Data::Touch(Touch::Raw {
+                index,
+                action: TouchAction::Cancel,
+                point,
+                pressure,
+                size,
+                ..
+            }) => self.finger_cancel(point.0, point.1, index, pressure, size),
+            Data::Window(Window::SizeChange(c)) => {
+                self.window_size_changed(c.current.width, c.current.height)
+            }
+            Data::Window(Window::ScaleFactorChanged(c)) => {
+                self.scale_factor_changed(c.current.scale_factor)
+            }
+            Data::Window(Window::Moved { position }) => self.window_moved(position),
+            Data::Window(Window::RedrawRequested { damaged_rect }) => {
+                self.window_damaged(damaged_rect)
+            }
+            Data::Window(Window::Focus) => self.window_focus(),
+            Data::Window(Window::Defocus) => self.window_defocus(),
+            Data::Lifecycle(l) => self.lifecycle_changed(l),
+            Data::Pen {
+                position,
+                pressure,
+                tilt,
+                rotation,
+                button,
+                eraser,
+                proximity,
+                ..
+            } => self.pen_changed(PenState {
+                position,
+                pressure,
+                tilt,
+                rotation,
+                button,
+                eraser,
+                proximity,
+            }),
+            Data::Gamepad(Gamepad::Connected { id, name }) => self.gamepad_connected(id, name),
+            Data::Gamepad(Gamepad::Disconnected { id }) => self.gamepad_disconnected(id),
+            Data::Move(Move::Gamepad {
+                id, axis, current, ..
+            }) => self.gamepad_axis_changed(id, axis, current),
+            d => self.broadcast(Event::new(d)),
+        }
+    }
+
     pub(crate) fn quit(&self) {
         self.broadcast(Event::new(Data::Quit));
     }
 
+    /// On Android the surface's destruction and the state saving reach the
+    /// listeners before it returns, the platform continues with them right
+    /// after.
+    pub(crate) fn lifecycle_changed(&self, l: Lifecycle) {
+        let e = Event::new(Data::Lifecycle(l));
+        #[cfg(any(target_os = "android", test))]
+        {
+            if let Lifecycle::SurfaceDestroyed | Lifecycle::SaveState = l {
+                return self.broadcast_and_wait(e);
+            }
+        }
+        self.broadcast(e);
+    }
+
     pub(crate) fn window_focus(&self) {
         self.broadcast(Event::new(Data::Window(Window::Focus)));
     }
@@ -624,11 +2136,857 @@ impl Engine {
 
 impl Drop for Engine {
     fn drop(&mut self) {
-        result_f!(self.sender.send(Event::new(Data::Terminate)));
-        if let Some(processor) = self.processor.take() {
-            result_f!(processor.join());
+        if let Some(t) = result_f!(self.processor_thread.lock()).take() {
+            self.stop_processor_thread(t);
         }
         #[cfg(feature = "verbose-log")]
         log_i!("Rust-Graphics Window library's Event Engine dropped.");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::sleep;
+
+    struct Recorder {
+        tag: i64,
+        stop: bool,
+        tags: Arc<Mutex<Vec<i64>>>,
+        events: Arc<Mutex<Vec<Event>>>,
+    }
+
+    impl Listener for Recorder {
+        fn on_event(&mut self, e: &Event) -> bool {
+            result_f!(self.tags.lock()).push(self.tag);
+            result_f!(self.events.lock()).push(e.clone());
+            self.stop
+        }
+    }
+
+    fn new_engine() -> Engine {
+        let engine = Engine::new();
+        engine.set_dispatch_mode(DispatchMode::CallerThread);
+        engine.init_window_aspects(200, 100, 1.0);
+        engine
+    }
+
+    /// The listener, its recorded events and its handle.
+    type AddedRecorder = (
+        Arc<RwLock<dyn Listener>>,
+        Arc<Mutex<Vec<Event>>>,
+        ListenerHandle,
+    );
+
+    fn add_recorder(
+        engine: &Engine,
+        priority: i64,
+        tag: i64,
+        stop: bool,
+        tags: &Arc<Mutex<Vec<i64>>>,
+    ) -> AddedRecorder {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let l: Arc<RwLock<dyn Listener>> = Arc::new(RwLock::new(Recorder {
+            tag,
+            stop,
+            tags: tags.clone(),
+            events: events.clone(),
+        }));
+        let h = engine.add(priority, EventCategory::all(), Arc::downgrade(&l));
+        (l, events, h)
+    }
+
+    fn size_changes(events: &[Event]) -> Vec<WindowSizeChange> {
+        events
+            .iter()
+            .filter_map(|e| match e.get_data() {
+                &Data::Window(Window::SizeChange(c)) => Some(c),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Polls the events that the processor thread records, the test fails
+    /// when the condition does not hold in a few seconds.
+    fn wait_for<F: Fn(&[Event]) -> bool>(events: &Arc<Mutex<Vec<Event>>>, f: F) {
+        let start = Instant::now();
+        while !f(&result_f!(events.lock())) {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "the events are not delivered"
+            );
+            sleep(Duration::from_millis(1));
+        }
+    }
+
+    fn assert_near(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn listeners_are_called_in_priority_order() {
+        let engine = new_engine();
+        let tags = Arc::new(Mutex::new(Vec::new()));
+        let _l3 = add_recorder(&engine, 3, 3, false, &tags);
+        let _l1 = add_recorder(&engine, 1, 1, false, &tags);
+        let _l2 = add_recorder(&engine, 2, 2, false, &tags);
+        let _l1b = add_recorder(&engine, 1, 10, false, &tags);
+        engine.quit();
+        engine.dispatch();
+        assert_eq!(*result_f!(tags.lock()), vec![1, 10, 2, 3]);
+    }
+
+    #[test]
+    fn listener_can_stop_propagation() {
+        let engine = new_engine();
+        let tags = Arc::new(Mutex::new(Vec::new()));
+        let _l1 = add_recorder(&engine, 1, 1, false, &tags);
+        let _l2 = add_recorder(&engine, 2, 2, true, &tags);
+        let _l3 = add_recorder(&engine, 3, 3, false, &tags);
+        engine.quit();
+        engine.dispatch();
+        assert_eq!(*result_f!(tags.lock()), vec![1, 2]);
+    }
+
+    #[test]
+    fn receivers_see_events_that_listeners_stopped() {
+        let engine = new_engine();
+        let tags = Arc::new(Mutex::new(Vec::new()));
+        let _l = add_recorder(&engine, 0, 0, true, &tags);
+        let receiver = engine.subscribe();
+        engine.quit();
+        engine.dispatch();
+        let events: Vec<Event> = receiver.drain().collect();
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0].get_data(), Data::Quit));
+    }
+
+    #[test]
+    fn uninitialized_and_invalid_aspects_stay_finite() {
+        let aspects = WindowAspects::default();
+        assert_eq!(aspects.get_scale_factor(), 1.0);
+        assert_eq!(aspects.get_logical_size(), (0.0, 0.0));
+        let engine = Engine::new();
+        engine.set_dispatch_mode(DispatchMode::CallerThread);
+        engine.finger_down(10, 20, 0, 1.0, 0.0);
+        let p = engine.input_state().get_touches()[&0].get_normalized_position();
+        assert_eq!(p, (0.0, 0.0));
+        engine.init_window_aspects(0, 100, 0.0);
+        let aspects = engine.get_window_aspects();
+        assert_eq!(aspects.get_scale_factor(), 1.0);
+        assert_eq!(aspects.get_ratio(), 0.0);
+        assert_eq!(aspects.get_normalized_width(), 0.0);
+        assert_eq!(aspects.get_normalized_height(), 0.0);
+        assert_eq!(aspects.get_logical_size(), (0.0, 100.0));
+        engine.scale_factor_changed(f64::NAN);
+        engine.scale_factor_changed(-1.0);
+        assert_eq!(engine.get_window_aspects().get_scale_factor(), 1.0);
+    }
+
+    #[test]
+    fn events_are_only_kept_after_the_first_drain() {
+        let engine = new_engine();
+        engine.quit();
+        engine.dispatch();
+        engine.quit();
+        let events: Vec<Event> = engine.drain().collect();
+        assert_eq!(events.len(), 1);
+        assert!(engine.drain().next().is_none());
+        engine.quit();
+        engine.dispatch();
+        assert_eq!(engine.drain().count(), 1);
+    }
+
+    #[test]
+    fn dropped_listeners_are_not_called_and_cleaned() {
+        let engine = new_engine();
+        let tags = Arc::new(Mutex::new(Vec::new()));
+        let (l1, _, _h1) = add_recorder(&engine, 1, 1, false, &tags);
+        let _l2 = add_recorder(&engine, 1, 2, false, &tags);
+        drop(l1);
+        engine.quit();
+        engine.dispatch();
+        assert_eq!(*result_f!(tags.lock()), vec![2]);
+        assert_eq!(engine.listeners.load().len(), 1);
+    }
+
+    #[test]
+    fn listeners_only_receive_their_interests() {
+        let engine = new_engine();
+        let tags = Arc::new(Mutex::new(Vec::new()));
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let l: Arc<RwLock<dyn Listener>> = Arc::new(RwLock::new(Recorder {
+            tag: 0,
+            stop: false,
+            tags: tags.clone(),
+            events: events.clone(),
+        }));
+        let _h = engine.add(0, EventCategory::KEYBOARD, Arc::downgrade(&l));
+        engine.button_pressed(Button::Mouse(Mouse::Left));
+        engine.button_pressed(Button::Keyboard(Keyboard::A));
+        engine.quit();
+        engine.dispatch();
+        let events = result_f!(events.lock());
+        assert_eq!(events.len(), 1);
+        assert!(events[0].get_data().category() == EventCategory::KEYBOARD);
+    }
+
+    #[test]
+    fn wheel_scrolls_are_mouse_events() {
+        let engine = new_engine();
+        let deltas = Arc::new(Mutex::new(Vec::new()));
+        let d = deltas.clone();
+        let _h = engine.on_category(0, EventCategory::MOUSE, move |e| {
+            if let &Data::Move(Move::Wheel { delta }) = e.get_data() {
+                result_f!(d.lock()).push(delta);
+            }
+            false
+        });
+        engine.wheel_scrolled((0.0, -1.5));
+        engine.button_pressed(Button::Keyboard(Keyboard::A));
+        engine.dispatch();
+        assert_eq!(*result_f!(deltas.lock()), vec![(0.0, -1.5)]);
+    }
+
+    #[test]
+    fn full_queue_keeps_every_event_in_order() {
+        let engine = new_engine();
+        let deltas = Arc::new(Mutex::new(Vec::new()));
+        let d = deltas.clone();
+        let _h = engine.on_category(
+            0,
+            EventCategory::MOUSE | EventCategory::KEYBOARD,
+            move |e| {
+                result_f!(d.lock()).push(match e.get_data() {
+                    &Data::Move(Move::Wheel { delta }) => delta.1,
+                    _ => -1.0,
+                });
+                false
+            },
+        );
+        let count = EVENT_QUEUE_CAPACITY * 2;
+        for i in 0..count {
+            engine.wheel_scrolled((0.0, (i + 1) as f64));
+        }
+        engine.button_pressed(Button::Keyboard(Keyboard::A));
+        engine.dispatch();
+        engine.wheel_scrolled((0.0, 0.5));
+        engine.dispatch();
+        let deltas = result_f!(deltas.lock());
+        assert_eq!(deltas.len(), count + 2);
+        assert!((0..count).all(|i| deltas[i] == (i + 1) as f64));
+        assert_eq!(deltas[count], -1.0);
+        assert_eq!(deltas[count + 1], 0.5);
+        assert!(engine
+            .input_state()
+            .is_pressed(&Button::Keyboard(Keyboard::A)));
+    }
+
+    #[test]
+    fn pens_are_normalized_and_only_sent_to_their_listeners() {
+        let engine = new_engine();
+        let pens = Arc::new(Mutex::new(Vec::new()));
+        let p = pens.clone();
+        let _h = engine.on_category(0, EventCategory::PEN, move |e| {
+            result_f!(p.lock()).push(e.get_data().clone());
+            false
+        });
+        engine.set_mouse_position((10, 10));
+        engine.pen_changed(PenState {
+            position: (150, 25),
+            pressure: 0.5,
+            tilt: (10.0, -20.0),
+            rotation: 90.0,
+            button: Some((PenButton::Tip, ButtonAction::Press)),
+            eraser: false,
+            proximity: true,
+        });
+        engine.dispatch();
+        let pens = result_f!(pens.lock());
+        assert_eq!(pens.len(), 1);
+        match pens[0] {
+            Data::Pen {
+                position,
+                normalized_position,
+                pressure,
+                button: Some((PenButton::Tip, ButtonAction::Press)),
+                eraser: false,
+                proximity: true,
+                ..
+            } => {
+                assert_eq!(position, (150, 25));
+                assert_near(normalized_position.0, 1.0);
+                assert_near(normalized_position.1, -0.5);
+                assert_near(pressure, 0.5);
+            }
+            _ => panic!("Unexpected event"),
+        }
+    }
+
+    #[test]
+    fn closures_receive_their_events_while_handles_live() {
+        let engine = new_engine();
+        let keys = Arc::new(Mutex::new(Vec::new()));
+        let k = keys.clone();
+        let key_handle = engine.on_key(0, Keyboard::Escape(0), move |a| {
+            result_f!(k.lock()).push(a.clone());
+            false
+        });
+        let sizes = Arc::new(Mutex::new(Vec::new()));
+        let s = sizes.clone();
+        let _resize_handle = engine.on_resize(0, move |c| {
+            result_f!(s.lock()).push(c.current.get_physical_size());
+            false
+        });
+        let count = Arc::new(AtomicU64::new(0));
+        let c = count.clone();
+        let _all_handle = engine.on(1, move |_| {
+            c.fetch_add(1, Ordering::Relaxed);
+            false
+        });
+        engine.button_pressed(Button::Keyboard(Keyboard::Escape(0)));
+        engine.button_pressed(Button::Keyboard(Keyboard::A));
+        engine.window_size_changed(300, 150);
+        engine.dispatch();
+        drop(key_handle);
+        engine.button_released(Button::Keyboard(Keyboard::Escape(0)));
+        engine.dispatch();
+        assert_eq!(result_f!(keys.lock()).len(), 1);
+        assert_eq!(*result_f!(sizes.lock()), vec![(300, 150)]);
+        assert_eq!(count.load(Ordering::Relaxed), 4);
+    }
+
+    #[test]
+    fn dropped_handles_remove_listeners() {
+        let engine = new_engine();
+        let tags = Arc::new(Mutex::new(Vec::new()));
+        let (_l1, _, h1) = add_recorder(&engine, 1, 1, false, &tags);
+        let (_l2, _, h2) = add_recorder(&engine, 1, 2, false, &tags);
+        drop(h1);
+        engine.quit();
+        engine.dispatch();
+        h2.remove();
+        engine.quit();
+        engine.dispatch();
+        assert_eq!(*result_f!(tags.lock()), vec![2]);
+        assert!(engine.listeners.load().is_empty());
+    }
+
+    #[test]
+    fn handles_change_priorities() {
+        let engine = new_engine();
+        let tags = Arc::new(Mutex::new(Vec::new()));
+        let (_l1, _, mut h1) = add_recorder(&engine, 1, 1, false, &tags);
+        let _l2 = add_recorder(&engine, 2, 2, false, &tags);
+        h1.set_priority(3);
+        assert_eq!(h1.get_priority(), 3);
+        engine.quit();
+        engine.dispatch();
+        assert_eq!(*result_f!(tags.lock()), vec![2, 1]);
+    }
+
+    #[test]
+    fn resizes_are_coalesced_in_caller_thread() {
+        let engine = new_engine();
+        let tags = Arc::new(Mutex::new(Vec::new()));
+        let (_l, events, _h) = add_recorder(&engine, 0, 0, false, &tags);
+        engine.window_size_changed(300, 100);
+        engine.window_size_changed(400, 200);
+        engine.window_size_changed(500, 250);
+        engine.dispatch();
+        let changes = size_changes(&result_f!(events.lock()));
+        assert_eq!(changes.len(), 1);
+        let c = &changes[0];
+        assert_eq!(c.previous.get_physical_size(), (200, 100));
+        assert_eq!(c.current.get_physical_size(), (500, 250));
+        assert_eq!(c.delta.get_physical_size(), (300, 150));
+        assert_eq!(engine.get_window_aspects().get_physical_size(), (500, 250));
+    }
+
+    #[test]
+    fn resizes_are_coalesced_in_processor_thread() {
+        let engine = new_engine();
+        engine.set_resize_coalescing(ResizeCoalescing::Debounce(Duration::from_secs(1)));
+        engine.set_dispatch_mode(DispatchMode::Threaded);
+        let tags = Arc::new(Mutex::new(Vec::new()));
+        let (_l, events, _h) = add_recorder(&engine, 0, 0, false, &tags);
+        engine.window_size_changed(300, 100);
+        engine.window_size_changed(400, 200);
+        wait_for(&events, |e| !size_changes(e).is_empty());
+        let changes = size_changes(&result_f!(events.lock()));
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].previous.get_physical_size(), (200, 100));
+        assert_eq!(changes[0].current.get_physical_size(), (400, 200));
+    }
+
+    #[test]
+    fn pending_resize_is_flushed_before_later_events() {
+        let engine = new_engine();
+        engine.set_resize_coalescing(ResizeCoalescing::Debounce(Duration::from_secs(10)));
+        engine.set_dispatch_mode(DispatchMode::Threaded);
+        let tags = Arc::new(Mutex::new(Vec::new()));
+        let (_l, events, _h) = add_recorder(&engine, 0, 0, false, &tags);
+        engine.window_size_changed(300, 100);
+        engine.window_size_changed(400, 200);
+        engine.button_pressed(Button::Keyboard(Keyboard::A));
+        wait_for(&events, |e| e.len() >= 2);
+        let events = result_f!(events.lock());
+        assert_eq!(events.len(), 2);
+        assert_eq!(size_changes(&events[..1]).len(), 1);
+        assert!(matches!(events[1].get_data(), Data::Button { .. }));
+    }
+
+    #[test]
+    fn pending_redraw_does_not_spin_before_the_resize_is_due() {
+        let engine = new_engine();
+        engine.set_resize_coalescing(ResizeCoalescing::Debounce(Duration::from_millis(100)));
+        let tags = Arc::new(Mutex::new(Vec::new()));
+        let (_l, events, _h) = add_recorder(&engine, 0, 0, false, &tags);
+        engine.window_size_changed(300, 100);
+        engine.window_damaged(Rect::new(0, 0, 10, 10));
+        let mut processor = result_f!(engine.processor.lock());
+        let mut steps = 0;
+        while size_changes(&result_f!(events.lock())).is_empty() {
+            assert!(processor.step(true));
+            steps += 1;
+            assert!(steps < 100, "the processor is spinning");
+        }
+    }
+
+    #[test]
+    fn destroyed_surface_is_delivered_before_returning_in_threaded_mode() {
+        let engine = new_engine();
+        engine.set_dispatch_mode(DispatchMode::Threaded);
+        let tags = Arc::new(Mutex::new(Vec::new()));
+        let (_l, events, _h) = add_recorder(&engine, 0, 0, false, &tags);
+        engine.window_size_changed(300, 100);
+        engine.lifecycle_changed(Lifecycle::SurfaceDestroyed);
+        let events = result_f!(events.lock());
+        assert_eq!(events.len(), 2);
+        assert_eq!(size_changes(&events[..1]).len(), 1);
+        assert!(matches!(
+            events[1].get_data(),
+            Data::Lifecycle(Lifecycle::SurfaceDestroyed)
+        ));
+    }
+
+    #[test]
+    fn max_latency_bounds_the_debounce() {
+        let engine = new_engine();
+        engine.set_resize_coalescing(ResizeCoalescing::MaxLatency {
+            debounce: Duration::from_secs(10),
+            max_latency: Duration::from_millis(50),
+        });
+        let tags = Arc::new(Mutex::new(Vec::new()));
+        let (_l, events, _h) = add_recorder(&engine, 0, 0, false, &tags);
+        let start = Instant::now();
+        engine.window_size_changed(300, 100);
+        let mut processor = result_f!(engine.processor.lock());
+        while size_changes(&result_f!(events.lock())).is_empty() {
+            assert!(processor.step(true));
+        }
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn immediate_resizes_are_not_coalesced() {
+        let engine = new_engine();
+        engine.set_resize_coalescing(ResizeCoalescing::Immediate);
+        let tags = Arc::new(Mutex::new(Vec::new()));
+        let (_l, events, _h) = add_recorder(&engine, 0, 0, false, &tags);
+        engine.window_size_changed(300, 100);
+        engine.window_size_changed(400, 200);
+        engine.dispatch();
+        let changes = size_changes(&result_f!(events.lock()));
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[1].previous.get_physical_size(), (300, 100));
+    }
+
+    #[test]
+    fn size_change_keeps_previous_and_current_in_order() {
+        let engine = new_engine();
+        let tags = Arc::new(Mutex::new(Vec::new()));
+        let (_l, events, _h) = add_recorder(&engine, 0, 0, false, &tags);
+        engine.window_size_changed(100, 200);
+        engine.dispatch();
+        engine.window_size_changed(400, 100);
+        engine.dispatch();
+        let changes = size_changes(&result_f!(events.lock()));
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].previous.get_physical_size(), (200, 100));
+        assert_eq!(changes[0].current.get_physical_size(), (100, 200));
+        assert_eq!(changes[1].previous.get_physical_size(), (100, 200));
+        assert_eq!(changes[1].current.get_physical_size(), (400, 100));
+        assert_eq!(changes[1].delta.get_physical_size(), (300, -100));
+        assert_eq!(changes[1].delta.get_smallest(), 0);
+        assert_near(changes[1].delta.get_ratio(), 4.0 - 0.5);
+    }
+
+    #[test]
+    fn window_aspects_are_normalized_by_the_smallest_side() {
+        let engine = new_engine();
+        let a = engine.get_window_aspects();
+        assert_eq!(a.get_smallest(), 100);
+        assert_near(a.get_ratio(), 2.0);
+        assert_near(a.get_normalized_width(), 2.0);
+        assert_near(a.get_normalized_height(), 1.0);
+        engine.scale_factor_changed(2.0);
+        let a = engine.get_window_aspects();
+        assert_eq!(a.get_physical_size(), (200, 100));
+        assert_near(a.get_logical_width(), 100.0);
+        assert_near(a.get_logical_height(), 50.0);
+    }
+
+    #[cfg(all(not(target_os = "android"), not(target_os = "ios")))]
+    #[test]
+    fn mouse_moves_are_normalized_around_the_center() {
+        let engine = new_engine();
+        engine.init_mouse_position((100, 50));
+        let tags = Arc::new(Mutex::new(Vec::new()));
+        let (_l, events, _h) = add_recorder(&engine, 0, 0, false, &tags);
+        engine.set_mouse_position((200, 100));
+        engine.dispatch();
+        let events = result_f!(events.lock());
+        match events[0].get_data() {
+            &Data::Move(Move::Mouse {
+                previous,
+                current,
+                delta,
+                normalized_previous,
+                normalized_current,
+                normalized_delta,
+            }) => {
+                assert_eq!(previous, (100, 50));
+                assert_eq!(current, (200, 100));
+                assert_eq!(delta, (100, 50));
+                assert_near(normalized_previous.0, 0.0);
+                assert_near(normalized_previous.1, 0.0);
+                assert_near(normalized_current.0, 2.0);
+                assert_near(normalized_current.1, 1.0);
+                assert_near(normalized_delta.0, 2.0);
+                assert_near(normalized_delta.1, 1.0);
+            }
+            _ => panic!("Unexpected event"),
+        }
+    }
+
+    #[test]
+    fn touch_moves_have_deltas_from_the_previous_position() {
+        let engine = new_engine();
+        let tags = Arc::new(Mutex::new(Vec::new()));
+        let (_l, events, _h) = add_recorder(&engine, 0, 0, false, &tags);
+        engine.finger_down(0, 0, 7, 1.0, 0.0);
+        engine.finger_move(50, 25, 7, 1.0, 0.0);
+        engine.finger_move(100, 50, 7, 1.0, 0.0);
+        engine.finger_up(100, 50, 7, 1.0, 0.0);
+        engine.dispatch();
+        let events = result_f!(events.lock());
+        let moves: Vec<_> = events
+            .iter()
+            .filter_map(|e| match e.get_data() {
+                Data::Move(Move::Touch {
+                    index,
+                    previous,
+                    delta,
+                    normalized_delta,
+                    ..
+                }) => {
+                    assert_eq!(*index, 7);
+                    Some((*previous, *delta, *normalized_delta))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(moves.len(), 2);
+        assert_eq!(moves[0].0, (0, 0));
+        assert_eq!(moves[0].1, (50, 25));
+        assert_eq!(moves[1].0, (50, 25));
+        assert_eq!(moves[1].1, (50, 25));
+        assert_near((moves[1].2).0, 1.0);
+        assert_near((moves[1].2).1, 0.5);
+        assert!(engine.input_state().get_touches().is_empty());
+    }
+
+    #[test]
+    fn touch_pressure_is_tracked_and_still_fingers_are_dropped() {
+        let engine = new_engine();
+        let tags = Arc::new(Mutex::new(Vec::new()));
+        let (_l, events, _h) = add_recorder(&engine, 0, 0, false, &tags);
+        engine.finger_down(10, 10, 0, 0.5, 0.1);
+        engine.finger_move(10, 10, 0, 0.5, 0.1);
+        engine.finger_move(10, 10, 0, 0.8, 0.1);
+        {
+            let state = engine.input_state();
+            let finger = unwrap_f!(state.get_touches().get(&0));
+            assert_near(finger.get_pressure(), 0.8);
+            assert_near(finger.get_size(), 0.1);
+        }
+        engine.dispatch();
+        let events = result_f!(events.lock());
+        let pressures: Vec<f64> = events
+            .iter()
+            .filter_map(|e| match e.get_data() {
+                &Data::Move(Move::Touch { pressure, .. }) => Some(pressure),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(pressures.len(), 1);
+        assert_near(pressures[0], 0.8);
+    }
+
+    fn gestures(events: &[Event]) -> Vec<(GestureState, TouchGesture)> {
+        events
+            .iter()
+            .filter_map(|e| match e.get_data() {
+                &Data::Touch(Touch::Gesture {
+                    ref state,
+                    ref data,
+                    ..
+                }) => Some((state.clone(), data.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn second_tap_makes_a_double_tap() {
+        let engine = new_engine();
+        let tags = Arc::new(Mutex::new(Vec::new()));
+        let (_l, events, _h) = add_recorder(&engine, 0, 0, false, &tags);
+        engine.finger_down(50, 50, 0, 1.0, 0.0);
+        engine.finger_up(50, 50, 0, 1.0, 0.0);
+        engine.finger_down(52, 51, 1, 1.0, 0.0);
+        engine.finger_up(52, 51, 1, 1.0, 0.0);
+        engine.dispatch();
+        let gestures = gestures(&result_f!(events.lock()));
+        assert_eq!(gestures.len(), 3);
+        match gestures[0] {
+            (GestureState::Ended, TouchGesture::Tap { index, point, .. }) => {
+                assert_eq!(index, 0);
+                assert_eq!(point, (50, 50));
+            }
+            _ => panic!("Unexpected gesture"),
+        }
+        match gestures[1] {
+            (GestureState::Ended, TouchGesture::Tap { index, .. }) => assert_eq!(index, 1),
+            _ => panic!("Unexpected gesture"),
+        }
+        match gestures[2] {
+            (GestureState::Ended, TouchGesture::DoubleTap { index, point, .. }) => {
+                assert_eq!(index, 1);
+                assert_eq!(point, (52, 51));
+            }
+            _ => panic!("Unexpected gesture"),
+        }
+    }
+
+    #[test]
+    fn held_finger_long_presses_on_the_next_frame() {
+        let engine = new_engine();
+        engine.set_gesture_config(GestureConfig {
+            long_press_duration: Duration::from_millis(20),
+            ..GestureConfig::default()
+        });
+        let tags = Arc::new(Mutex::new(Vec::new()));
+        let (_l, events, _h) = add_recorder(&engine, 0, 0, false, &tags);
+        engine.finger_down(50, 50, 2, 1.0, 0.0);
+        sleep(Duration::from_millis(40));
+        engine.start_frame();
+        engine.finger_up(50, 50, 2, 1.0, 0.0);
+        engine.dispatch();
+        let gestures = gestures(&result_f!(events.lock()));
+        assert_eq!(gestures.len(), 2);
+        match gestures[0] {
+            (GestureState::Started, TouchGesture::LongPress { index, point, .. }) => {
+                assert_eq!(index, 2);
+                assert_eq!(point, (50, 50));
+            }
+            _ => panic!("Unexpected gesture"),
+        }
+        match gestures[1] {
+            (GestureState::Ended, TouchGesture::LongPress { index, .. }) => assert_eq!(index, 2),
+            _ => panic!("Unexpected gesture"),
+        }
+    }
+
+    #[test]
+    fn canceled_touches_cancel_their_gestures() {
+        let engine = new_engine();
+        let tags = Arc::new(Mutex::new(Vec::new()));
+        let (_l, events, _h) = add_recorder(&engine, 0, 0, false, &tags);
+        engine.finger_down(50, 50, 0, 1.0, 0.0);
+        engine.finger_cancel(50, 50, 0, 1.0, 0.0);
+        engine.finger_down(100, 50, 1, 1.0, 0.0);
+        engine.finger_move(130, 50, 1, 1.0, 0.0);
+        engine.finger_cancel(130, 50, 1, 1.0, 0.0);
+        engine.dispatch();
+        assert!(engine.input_state().get_touches().is_empty());
+        let gestures = gestures(&result_f!(events.lock()));
+        assert_eq!(gestures.len(), 2);
+        match gestures[1] {
+            (GestureState::Canceled, TouchGesture::Drag { index, current, .. }) => {
+                assert_eq!(index, 1);
+                assert_eq!(current, (130, 50));
+            }
+            _ => panic!("Unexpected gesture"),
+        }
+    }
+
+    #[test]
+    fn drags_start_after_the_slop() {
+        let engine = new_engine();
+        let tags = Arc::new(Mutex::new(Vec::new()));
+        let (_l, events, _h) = add_recorder(&engine, 0, 0, false, &tags);
+        engine.finger_down(100, 50, 3, 1.0, 0.0);
+        engine.finger_move(101, 50, 3, 1.0, 0.0);
+        engine.finger_move(120, 50, 3, 1.0, 0.0);
+        engine.finger_move(130, 60, 3, 1.0, 0.0);
+        engine.finger_up(130, 60, 3, 1.0, 0.0);
+        engine.dispatch();
+        let gestures = gestures(&result_f!(events.lock()));
+        let drags: Vec<_> = gestures
+            .into_iter()
+            .map(|g| match g {
+                (
+                    state,
+                    TouchGesture::Drag {
+                        index,
+                        start,
+                        delta,
+                        ..
+                    },
+                ) => {
+                    assert_eq!(index, 3);
+                    (state, start, delta)
+                }
+                _ => panic!("Unexpected gesture"),
+            })
+            .collect();
+        assert_eq!(drags.len(), 3);
+        assert!(matches!(drags[0].0, GestureState::Started));
+        assert_eq!(drags[0].1, (100, 50));
+        assert_eq!(drags[0].2, (20, 0));
+        assert!(matches!(drags[1].0, GestureState::InMiddle));
+        assert_eq!(drags[1].2, (10, 10));
+        assert!(matches!(drags[2].0, GestureState::Ended));
+    }
+
+    #[test]
+    fn two_fingers_scale_and_rotate() {
+        let engine = new_engine();
+        let tags = Arc::new(Mutex::new(Vec::new()));
+        let (_l, events, _h) = add_recorder(&engine, 0, 0, false, &tags);
+        engine.finger_down(80, 50, 0, 1.0, 0.0);
+        engine.finger_move(70, 50, 0, 1.0, 0.0);
+        engine.finger_down(120, 50, 1, 1.0, 0.0);
+        engine.finger_move(140, 50, 1, 1.0, 0.0);
+        engine.finger_move(70, 0, 1, 1.0, 0.0);
+        engine.finger_up(70, 0, 1, 1.0, 0.0);
+        engine.finger_up(70, 50, 0, 1.0, 0.0);
+        engine.dispatch();
+        let gestures = gestures(&result_f!(events.lock()));
+        let mut scales = Vec::new();
+        let mut rotations = Vec::new();
+        for g in gestures {
+            match g {
+                (_, TouchGesture::Drag { .. }) => (),
+                (state, TouchGesture::Scale { start, current, .. }) => {
+                    scales.push((state, start, current))
+                }
+                (state, TouchGesture::Rotate { start, current, .. }) => {
+                    rotations.push((state, start, current))
+                }
+                _ => panic!("Unexpected gesture"),
+            }
+        }
+        assert_eq!(scales.len(), 3);
+        assert_eq!((scales[0].1, scales[0].2), (50, 70));
+        assert_eq!(scales[2].2, 50);
+        assert!(matches!(scales[2].0, GestureState::Ended));
+        assert_eq!(rotations.len(), 2);
+        assert!(matches!(rotations[0].0, GestureState::Started));
+        assert_near(rotations[0].1, 0.0);
+        assert_near(rotations[0].2, -std::f64::consts::FRAC_PI_2);
+    }
+
+    #[test]
+    fn gamepad_disconnection_releases_its_buttons() {
+        let engine = new_engine();
+        let tags = Arc::new(Mutex::new(Vec::new()));
+        let (_l, events, _h) = add_recorder(&engine, 0, 0, false, &tags);
+        let south = Button::Gamepad {
+            id: 1,
+            button: GamepadButton::South,
+        };
+        engine.gamepad_connected(1, "Pad".to_string());
+        engine.button_pressed(south.clone());
+        engine.gamepad_axis_changed(1, GamepadAxis::LeftX, 0.5);
+        engine.gamepad_axis_changed(1, GamepadAxis::LeftX, 0.5);
+        let state = engine.input_state();
+        assert!(state.is_pressed(&south));
+        assert_near(state.get_gamepads()[&1].get_axis(GamepadAxis::LeftX), 0.5);
+        engine.gamepad_disconnected(1);
+        engine.dispatch();
+        assert!(!engine.input_state().is_pressed(&south));
+        assert!(engine.input_state().get_gamepads().is_empty());
+        let events = result_f!(events.lock());
+        assert_eq!(events.len(), 5);
+        assert!(events
+            .iter()
+            .all(|e| e.get_data().category() == EventCategory::GAMEPAD));
+        assert!(matches!(
+            events[3].get_data(),
+            Data::Button {
+                action: ButtonAction::Release,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn redraw_requests_are_merged_and_delivered_after_resize() {
+        let engine = new_engine();
+        let tags = Arc::new(Mutex::new(Vec::new()));
+        let (_l, events, _h) = add_recorder(&engine, 0, 0, false, &tags);
+        engine.window_damaged(Rect::new(10, 10, 10, 10));
+        engine.window_damaged(Rect::new(30, 5, 10, 10));
+        engine.window_size_changed(300, 150);
+        engine.dispatch();
+        engine.request_redraw();
+        engine.request_redraw();
+        engine.dispatch();
+        let events = result_f!(events.lock());
+        let kinds: Vec<Option<Rect>> = events
+            .iter()
+            .map(|e| match e.get_data() {
+                &Data::Window(Window::RedrawRequested { damaged_rect }) => Some(damaged_rect),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(kinds.len(), 3);
+        assert!(kinds[0].is_none());
+        assert!(kinds[1] == Some(Rect::new(0, 0, 300, 150)));
+        assert!(kinds[2] == Some(Rect::new(0, 0, 300, 150)));
+    }
+
+    #[test]
+    fn just_pressed_buttons_last_for_a_frame() {
+        let engine = new_engine();
+        let space = Button::Keyboard(Keyboard::Space(0));
+        engine.button_pressed(space.clone());
+        let s = engine.input_state();
+        assert!(s.is_pressed(&space));
+        assert!(s.just_pressed(&space));
+        engine.start_frame();
+        let s = engine.input_state();
+        assert!(s.is_pressed(&space));
+        assert!(!s.just_pressed(&space));
+        engine.inject(Data::Button {
+            button: space.clone(),
+            action: ButtonAction::Release,
+        });
+        let s = engine.input_state();
+        assert!(!s.is_pressed(&space));
+        assert!(s.just_released(&space));
+    }
+}
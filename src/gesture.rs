@@ -0,0 +1,512 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+use {
+    crate::event::{FingerIndexType, GestureConfig, GestureState, Touch, TouchGesture, TouchState},
+    std::{collections::BTreeMap, f64::consts::PI},
+};
+
+fn distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    ((b.0 - a.0) * (b.0 - a.0) + (b.1 - a.1) * (b.1 - a.1)).sqrt()
+}
+
+fn to_f64(p: (i64, i64)) -> (f64, f64) {
+    (p.0 as f64, p.1 as f64)
+}
+
+/// Brings the angle into (-PI, PI].
+fn wrap_angle(mut a: f64) -> f64 {
+    while a > PI {
+        a -= 2.0 * PI;
+    }
+    while a <= -PI {
+        a += 2.0 * PI;
+    }
+    a
+}
+
+fn gesture(start_time: Instant, now: Instant, state: GestureState, data: TouchGesture) -> Touch {
+    Touch::Gesture {
+        start_time,
+        duration: now.duration_since(start_time),
+        state,
+        data,
+    }
+}
+
+enum Phase {
+    Pending,
+    Dragging,
+    LongPressing,
+}
+
+struct Single {
+    index: FingerIndexType,
+    phase: Phase,
+    start: TouchState,
+    current: TouchState,
+    start_time: Instant,
+    /// Start of the drag or the long press.
+    phase_time: Instant,
+}
+
+impl Single {
+    fn point(
+        &self,
+        g: fn(FingerIndexType, (i64, i64), (f64, f64)) -> TouchGesture,
+    ) -> TouchGesture {
+        g(
+            self.index,
+            self.current.get_position(),
+            self.current.get_normalized_position(),
+        )
+    }
+
+    fn drag(&self, previous: &TouchState) -> TouchGesture {
+        let p = previous.get_position();
+        let c = self.current.get_position();
+        let np = previous.get_normalized_position();
+        let nc = self.current.get_normalized_position();
+        TouchGesture::Drag {
+            index: self.index,
+            start: self.start.get_position(),
+            previous: p,
+            current: c,
+            delta: (c.0 - p.0, c.1 - p.1),
+            normalized_start: self.start.get_normalized_position(),
+            normalized_previous: np,
+            normalized_current: nc,
+            normalized_delta: (nc.0 - np.0, nc.1 - np.1),
+        }
+    }
+}
+
+fn long_press(
+    index: FingerIndexType,
+    point: (i64, i64),
+    normalized_point: (f64, f64),
+) -> TouchGesture {
+    TouchGesture::LongPress {
+        index,
+        point,
+        normalized_point,
+    }
+}
+
+fn tap(index: FingerIndexType, point: (i64, i64), normalized_point: (f64, f64)) -> TouchGesture {
+    TouchGesture::Tap {
+        index,
+        point,
+        normalized_point,
+    }
+}
+
+fn double_tap(
+    index: FingerIndexType,
+    point: (i64, i64),
+    normalized_point: (f64, f64),
+) -> TouchGesture {
+    TouchGesture::DoubleTap {
+        index,
+        point,
+        normalized_point,
+    }
+}
+
+/// Measure of a two finger gesture, with its value at the start and at the
+/// last sent event, `None` start time means the gesture has not started. The
+/// normalized values are only used by the scale.
+struct Measure {
+    start_time: Option<Instant>,
+    start: f64,
+    previous: f64,
+    normalized_start: f64,
+    normalized_previous: f64,
+}
+
+impl Measure {
+    fn new(value: f64, normalized_value: f64) -> Self {
+        Self {
+            start_time: None,
+            start: value,
+            previous: value,
+            normalized_start: normalized_value,
+            normalized_previous: normalized_value,
+        }
+    }
+}
+
+struct Pair {
+    first: (FingerIndexType, TouchState),
+    second: (FingerIndexType, TouchState),
+    scale: Measure,
+    rotation: Measure,
+}
+
+impl Pair {
+    fn new(first: (FingerIndexType, TouchState), second: (FingerIndexType, TouchState)) -> Self {
+        let mut result = Self {
+            first,
+            second,
+            scale: Measure::new(0.0, 0.0),
+            rotation: Measure::new(0.0, 0.0),
+        };
+        let (d, nd, a) = result.measure();
+        result.scale = Measure::new(d, nd);
+        result.rotation = Measure::new(a, 0.0);
+        result
+    }
+
+    fn measure(&self) -> (f64, f64, f64) {
+        let f = to_f64(self.first.1.get_position());
+        let s = to_f64(self.second.1.get_position());
+        (
+            distance(f, s),
+            distance(
+                self.first.1.get_normalized_position(),
+                self.second.1.get_normalized_position(),
+            ),
+            (s.1 - f.1).atan2(s.0 - f.0),
+        )
+    }
+
+    fn scale(&self, current: f64, normalized_current: f64) -> TouchGesture {
+        let m = &self.scale;
+        TouchGesture::Scale {
+            first: self.first.clone(),
+            second: self.second.clone(),
+            start: m.start.round() as i64,
+            previous: m.previous.round() as i64,
+            current: current.round() as i64,
+            delta: current.round() as i64 - m.previous.round() as i64,
+            normalized_start: m.normalized_start,
+            normalized_previous: m.normalized_previous,
+            normalized_current,
+            normalized_delta: normalized_current - m.normalized_previous,
+        }
+    }
+
+    fn rotate(&self, current: f64) -> TouchGesture {
+        let m = &self.rotation;
+        TouchGesture::Rotate {
+            first: self.first.clone(),
+            second: self.second.clone(),
+            start: m.start,
+            previous: m.previous,
+            current,
+            delta: wrap_angle(current - m.previous),
+        }
+    }
+
+    /// Sends the end of the started gestures.
+    fn finish(&self, state: GestureState, now: Instant, result: &mut Vec<Touch>) {
+        let (d, nd, a) = self.measure();
+        if let Some(t) = self.scale.start_time {
+            result.push(gesture(t, now, state.clone(), self.scale(d, nd)));
+        }
+        if let Some(t) = self.rotation.start_time {
+            result.push(gesture(t, now, state, self.rotate(a)));
+        }
+    }
+}
+
+/// Turns the raw touches into the gestures, it keeps a gesture of one finger
+/// or a gesture of two fingers, more fingers cancel the current gesture.
+pub(crate) struct Recognizer {
+    config: GestureConfig,
+    single: Option<Single>,
+    pair: Option<Pair>,
+    /// Start, end and position of the last tap.
+    last_tap: Option<(Instant, Instant, (f64, f64))>,
+}
+
+impl Recognizer {
+    pub(crate) fn new() -> Self {
+        Self {
+            config: GestureConfig::default(),
+            single: None,
+            pair: None,
+            last_tap: None,
+        }
+    }
+
+    pub(crate) fn get_config(&self) -> GestureConfig {
+        self.config
+    }
+
+    pub(crate) fn set_config(&mut self, config: GestureConfig) {
+        self.config = config;
+    }
+
+    /// Must be called after the finger has been added to the fingers.
+    pub(crate) fn finger_down(
+        &mut self,
+        index: FingerIndexType,
+        fingers: &BTreeMap<FingerIndexType, TouchState>,
+        now: Instant,
+    ) -> Vec<Touch> {
+        let mut result = self.tick(now);
+        self.cancel(now, &mut result);
+        let touch = match fingers.get(&index) {
+            Some(t) => t.clone(),
+            None => return result,
+        };
+        if fingers.len() > 1 {
+            self.last_tap = None;
+        }
+        match fingers.len() {
+            1 => {
+                self.single = Some(Single {
+                    index,
+                    phase: Phase::Pending,
+                    start: touch.clone(),
+                    current: touch,
+                    start_time: now,
+                    phase_time: now,
+                })
+            }
+            2 => {
+                if let Some((&other, other_touch)) = fingers.iter().find(|&(&i, _)| i != index) {
+                    self.pair = Some(Pair::new((other, other_touch.clone()), (index, touch)));
+                }
+            }
+            _ => {}
+        }
+        result
+    }
+
+    pub(crate) fn finger_move(
+        &mut self,
+        index: FingerIndexType,
+        touch: &TouchState,
+        now: Instant,
+    ) -> Vec<Touch> {
+        let mut result = self.tick(now);
+        let config = self.config;
+        if let Some(s) = self.single.as_mut() {
+            if s.index == index {
+                let previous = std::mem::replace(&mut s.current, touch.clone());
+                match s.phase {
+                    Phase::Pending => {
+                        if distance(
+                            s.start.get_normalized_position(),
+                            touch.get_normalized_position(),
+                        ) > config.touch_slop
+                        {
+                            s.phase = Phase::Dragging;
+                            s.phase_time = now;
+                            let start = s.start.clone();
+                            result.push(gesture(now, now, GestureState::Started, s.drag(&start)));
+                        }
+                    }
+                    Phase::Dragging => {
+                        result.push(gesture(
+                            s.phase_time,
+                            now,
+                            GestureState::InMiddle,
+                            s.drag(&previous),
+                        ));
+                    }
+                    Phase::LongPressing => {}
+                }
+            }
+        }
+        if let Some(p) = self.pair.as_mut() {
+            if p.first.0 == index {
+                p.first.1 = touch.clone();
+            } else if p.second.0 == index {
+                p.second.1 = touch.clone();
+            } else {
+                return result;
+            }
+            let (d, nd, a) = p.measure();
+            if let Some(t) = p.scale.start_time {
+                result.push(gesture(t, now, GestureState::InMiddle, p.scale(d, nd)));
+            } else if (nd - p.scale.normalized_start).abs() > config.touch_slop {
+                p.scale.start_time = Some(now);
+                result.push(gesture(now, now, GestureState::Started, p.scale(d, nd)));
+            }
+            if p.scale.start_time.is_some() {
+                p.scale.previous = d;
+                p.scale.normalized_previous = nd;
+            }
+            if let Some(t) = p.rotation.start_time {
+                result.push(gesture(t, now, GestureState::InMiddle, p.rotate(a)));
+            } else if wrap_angle(a - p.rotation.start).abs() > config.rotation_threshold {
+                p.rotation.start_time = Some(now);
+                result.push(gesture(now, now, GestureState::Started, p.rotate(a)));
+            }
+            if p.rotation.start_time.is_some() {
+                p.rotation.previous = a;
+            }
+        }
+        result
+    }
+
+    /// Must be called after the finger has been removed from the fingers.
+    pub(crate) fn finger_up(
+        &mut self,
+        index: FingerIndexType,
+        touch: &TouchState,
+        now: Instant,
+    ) -> Vec<Touch> {
+        let mut result = self.tick(now);
+        match self.single.take() {
+            Some(mut s) if s.index == index => {
+                let previous = std::mem::replace(&mut s.current, touch.clone());
+                match s.phase {
+                    Phase::Pending => {
+                        if now.duration_since(s.start_time) <= self.config.tap_max_duration {
+                            result.push(gesture(
+                                s.start_time,
+                                now,
+                                GestureState::Ended,
+                                s.point(tap),
+                            ));
+                            self.double_tap(&s, now, &mut result);
+                        }
+                    }
+                    Phase::Dragging => {
+                        result.push(gesture(
+                            s.phase_time,
+                            now,
+                            GestureState::Ended,
+                            s.drag(&previous),
+                        ));
+                    }
+                    Phase::LongPressing => {
+                        result.push(gesture(
+                            s.phase_time,
+                            now,
+                            GestureState::Ended,
+                            s.point(long_press),
+                        ));
+                    }
+                }
+            }
+            s => self.single = s,
+        }
+        let ended = match self.pair.as_mut() {
+            Some(p) if p.first.0 == index => {
+                p.first.1 = touch.clone();
+                true
+            }
+            Some(p) if p.second.0 == index => {
+                p.second.1 = touch.clone();
+                true
+            }
+            _ => false,
+        };
+        if ended {
+            if let Some(p) = self.pair.take() {
+                p.finish(GestureState::Ended, now, &mut result);
+            }
+        }
+        result
+    }
+
+    /// Must be called after the finger has been removed from the fingers, the
+    /// gestures of the finger are canceled and it does not count as a tap.
+    pub(crate) fn finger_cancel(
+        &mut self,
+        index: FingerIndexType,
+        touch: &TouchState,
+        now: Instant,
+    ) -> Vec<Touch> {
+        let mut result = self.tick(now);
+        let single = self
+            .single
+            .as_ref()
+            .map(|s| s.index == index)
+            .unwrap_or(false);
+        let pair = match self.pair.as_mut() {
+            Some(p) if p.first.0 == index => {
+                p.first.1 = touch.clone();
+                true
+            }
+            Some(p) if p.second.0 == index => {
+                p.second.1 = touch.clone();
+                true
+            }
+            _ => false,
+        };
+        if single || pair {
+            if let Some(s) = self.single.as_mut() {
+                s.current = touch.clone();
+            }
+            self.last_tap = None;
+            self.cancel(now, &mut result);
+        }
+        result
+    }
+
+    /// Starts the long press of a finger that has been held long enough.
+    pub(crate) fn tick(&mut self, now: Instant) -> Vec<Touch> {
+        let mut result = Vec::new();
+        let duration = self.config.long_press_duration;
+        if let Some(s) = self.single.as_mut() {
+            if let Phase::Pending = s.phase {
+                if now.duration_since(s.start_time) >= duration {
+                    s.phase = Phase::LongPressing;
+                    s.phase_time = s.start_time + duration;
+                    result.push(gesture(
+                        s.phase_time,
+                        now,
+                        GestureState::Started,
+                        s.point(long_press),
+                    ));
+                }
+            }
+        }
+        result
+    }
+
+    fn double_tap(&mut self, s: &Single, now: Instant, result: &mut Vec<Touch>) {
+        let point = s.current.get_normalized_position();
+        match self.last_tap.take() {
+            Some((t, end, p))
+                if s.start_time.duration_since(end) <= self.config.double_tap_interval
+                    && distance(p, point) <= self.config.double_tap_slop =>
+            {
+                result.push(gesture(t, now, GestureState::Ended, s.point(double_tap)));
+            }
+            _ => self.last_tap = Some((s.start_time, now, point)),
+        }
+    }
+
+    /// A new finger or a canceled one cancels the gestures in progress.
+    fn cancel(&mut self, now: Instant, result: &mut Vec<Touch>) {
+        if let Some(s) = self.single.take() {
+            match s.phase {
+                Phase::Pending => {}
+                Phase::Dragging => {
+                    let current = s.current.clone();
+                    result.push(gesture(
+                        s.phase_time,
+                        now,
+                        GestureState::Canceled,
+                        s.drag(&current),
+                    ));
+                }
+                Phase::LongPressing => {
+                    result.push(gesture(
+                        s.phase_time,
+                        now,
+                        GestureState::Canceled,
+                        s.point(long_press),
+                    ));
+                }
+            }
+        }
+        if let Some(p) = self.pair.take() {
+            p.finish(GestureState::Canceled, now, result);
+        }
+    }
+}
+
+impl Default for Recognizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,396 @@
+use {
+    crate::event::*,
+    log::{log_e, result_f},
+    std::{
+        cmp::max,
+        sync::{Arc, Mutex, MutexGuard},
+    },
+};
+
+/// An offscreen software framebuffer, pixels are 0xAARRGGBB and rows are
+/// stored from top to bottom.
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<u32>,
+}
+
+impl Framebuffer {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; width * height],
+        }
+    }
+
+    fn resize(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+        self.pixels = vec![0; width * height];
+    }
+
+    pub fn get_size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    pub fn get_pixels(&self) -> &[u32] {
+        &self.pixels
+    }
+
+    pub fn get_pixels_mut(&mut self) -> &mut [u32] {
+        &mut self.pixels
+    }
+
+    pub fn get_pixel(&self, x: usize, y: usize) -> Option<u32> {
+        if x < self.width && y < self.height {
+            Some(self.pixels[y * self.width + x])
+        } else {
+            None
+        }
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: u32) {
+        if x < self.width && y < self.height {
+            self.pixels[y * self.width + x] = color;
+        }
+    }
+
+    pub fn clear(&mut self, color: u32) {
+        for p in &mut self.pixels {
+            *p = color;
+        }
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "debug-derive", derive(Debug))]
+pub struct Config {
+    pub width: i64,
+    pub height: i64,
+    pub scale_factor: f64,
+    /// Creates the offscreen framebuffer with the size of the window.
+    pub framebuffer: bool,
+    /// Caller thread is the default for having deterministic tests.
+    pub dispatch_mode: DispatchMode,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            width: 1000,
+            height: 500,
+            scale_factor: 1.0,
+            framebuffer: false,
+            dispatch_mode: DispatchMode::CallerThread,
+        }
+    }
+}
+
+/// A window without any OS surface, inputs are injected by the caller and go
+/// through the same event engine as the platform windows.
+pub struct Window {
+    framebuffer: Option<Mutex<Framebuffer>>,
+    event_engine: Engine,
+}
+
+impl Window {
+    pub fn new(_: ()) -> Arc<Self> {
+        Self::new_with_config(Config::default())
+    }
+
+    /// Non-positive sizes are clamped to 1.
+    pub fn new_with_config(config: Config) -> Arc<Self> {
+        if config.width <= 0 || config.height <= 0 {
+            log_e!("Invalid window size: {}x{}", config.width, config.height);
+        }
+        let width = max(config.width, 1);
+        let height = max(config.height, 1);
+        let event_engine = Engine::new();
+        event_engine.set_dispatch_mode(config.dispatch_mode);
+        event_engine.init_window_aspects(width, height, config.scale_factor);
+        event_engine.init_window_position((0, 0));
+        #[cfg(all(not(target_os = "android"), not(target_os = "ios")))]
+        event_engine.init_mouse_position((0, 0));
+        let framebuffer = if config.framebuffer {
+            Some(Mutex::new(Framebuffer::new(
+                width as usize,
+                height as usize,
+            )))
+        } else {
+            None
+        };
+        Arc::new(Self {
+            framebuffer,
+            event_engine,
+        })
+    }
+
+    pub fn fetch_events(&self) {
+        self.event_engine.start_frame();
+        self.event_engine.dispatch();
+    }
+
+    pub fn get_event_engine(&self) -> &Engine {
+        &self.event_engine
+    }
+
+    pub fn request_redraw(&self) {
+        self.event_engine.request_redraw();
+    }
+
+    pub fn get_framebuffer<'a>(&'a self) -> Option<MutexGuard<'a, Framebuffer>> {
+        self.framebuffer.as_ref().map(|f| result_f!(f.lock()))
+    }
+
+    /// Returns a copy of the framebuffer's pixels.
+    pub fn read_pixels(&self) -> Option<Vec<u32>> {
+        self.get_framebuffer().map(|f| f.get_pixels().to_vec())
+    }
+
+    pub fn inject_button_press(&self, b: Button) {
+        self.event_engine.button_pressed(b);
+    }
+
+    pub fn inject_button_release(&self, b: Button) {
+        self.event_engine.button_released(b);
+    }
+
+    pub fn inject_key_press(&self, k: Keyboard) {
+        self.inject_button_press(Button::Keyboard(k));
+    }
+
+    pub fn inject_key_release(&self, k: Keyboard) {
+        self.inject_button_release(Button::Keyboard(k));
+    }
+
+    #[cfg(all(not(target_os = "android"), not(target_os = "ios")))]
+    pub fn inject_mouse_move(&self, x: i64, y: i64) {
+        self.event_engine.set_mouse_position((x, y));
+    }
+
+    #[cfg(all(not(target_os = "android"), not(target_os = "ios")))]
+    pub fn inject_wheel(&self, dx: f64, dy: f64) {
+        self.event_engine.wheel_scrolled((dx, dy));
+    }
+
+    pub fn inject_touch_down(&self, x: i64, y: i64, index: FingerIndexType) {
+        self.event_engine.finger_down(x, y, index, 1.0, 0.0);
+    }
+
+    pub fn inject_touch_move(&self, x: i64, y: i64, index: FingerIndexType) {
+        self.event_engine.finger_move(x, y, index, 1.0, 0.0);
+    }
+
+    pub fn inject_touch_up(&self, x: i64, y: i64, index: FingerIndexType) {
+        self.event_engine.finger_up(x, y, index, 1.0, 0.0);
+    }
+
+    pub fn inject_pen(&self, pen: PenState) {
+        self.event_engine.pen_changed(pen);
+    }
+
+    pub fn inject_gamepad_connect(&self, id: GamepadId, name: &str) {
+        self.event_engine.gamepad_connected(id, name.to_string());
+    }
+
+    pub fn inject_gamepad_disconnect(&self, id: GamepadId) {
+        self.event_engine.gamepad_disconnected(id);
+    }
+
+    pub fn inject_gamepad_button_press(&self, id: GamepadId, button: GamepadButton) {
+        self.inject_button_press(Button::Gamepad { id, button });
+    }
+
+    pub fn inject_gamepad_button_release(&self, id: GamepadId, button: GamepadButton) {
+        self.inject_button_release(Button::Gamepad { id, button });
+    }
+
+    pub fn inject_gamepad_axis(&self, id: GamepadId, axis: GamepadAxis, value: f64) {
+        self.event_engine.gamepad_axis_changed(id, axis, value);
+    }
+
+    /// The framebuffer is resized too and its content is cleared, it is kept
+    /// when the size does not change.
+    pub fn inject_resize(&self, width: i64, height: i64) {
+        if width <= 0 || height <= 0 {
+            log_e!("Invalid window size: {}x{}", width, height);
+            return;
+        }
+        let size = (width as usize, height as usize);
+        if let Some(mut f) = self.get_framebuffer() {
+            if f.get_size() != size {
+                f.resize(size.0, size.1);
+            }
+        }
+        self.event_engine.window_size_changed(width, height);
+    }
+
+    pub fn inject_damage(&self, damaged_rect: Rect) {
+        self.event_engine.window_damaged(damaged_rect);
+    }
+
+    pub fn inject_scale_factor(&self, scale_factor: f64) {
+        self.event_engine.scale_factor_changed(scale_factor);
+    }
+
+    pub fn inject_move(&self, x: i64, y: i64) {
+        self.event_engine.window_moved((x, y));
+    }
+
+    pub fn inject_focus(&self) {
+        self.event_engine.window_focus();
+    }
+
+    pub fn inject_defocus(&self) {
+        self.event_engine.window_defocus();
+    }
+
+    pub fn inject_quit(&self) {
+        self.event_engine.quit();
+    }
+
+    pub fn inject_lifecycle(&self, l: Lifecycle) {
+        self.event_engine.lifecycle_changed(l);
+    }
+
+    #[cfg(feature = "gl")]
+    pub fn swap(&self) {}
+
+    /// There is no GL context in the headless window.
+    #[cfg(feature = "gl")]
+    pub fn get_gl_function<T>(&self, _: &str) -> Option<T> {
+        None
+    }
+}
+
+#[cfg(feature = "debug-derive")]
+impl std::fmt::Debug for Window {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Headless-Window")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, log::unwrap_f};
+
+    fn new_window(width: i64, height: i64) -> Arc<Window> {
+        Window::new_with_config(Config {
+            width,
+            height,
+            framebuffer: true,
+            ..Config::default()
+        })
+    }
+
+    #[test]
+    fn config_sizes_the_window_and_the_framebuffer() {
+        let window = new_window(30, 20);
+        let aspects = window.get_event_engine().get_window_aspects();
+        assert_eq!((aspects.get_width(), aspects.get_height()), (30, 20));
+        assert_eq!(unwrap_f!(window.get_framebuffer()).get_size(), (30, 20));
+        let pixels = unwrap_f!(window.read_pixels());
+        assert_eq!(pixels.len(), 600);
+        assert!(pixels.iter().all(|&p| p == 0));
+        assert!(Window::new(()).read_pixels().is_none());
+    }
+
+    #[test]
+    fn non_positive_sizes_are_clamped() {
+        let window = new_window(-5, 0);
+        let aspects = window.get_event_engine().get_window_aspects();
+        assert_eq!((aspects.get_width(), aspects.get_height()), (1, 1));
+        assert_eq!(unwrap_f!(window.get_framebuffer()).get_size(), (1, 1));
+    }
+
+    #[test]
+    fn framebuffer_is_reallocated_only_when_the_size_changes() {
+        let window = new_window(30, 20);
+        unwrap_f!(window.get_framebuffer()).set_pixel(2, 1, 0xff00_ff00);
+        window.inject_resize(30, 20);
+        assert_eq!(unwrap_f!(window.read_pixels())[32], 0xff00_ff00);
+        window.inject_resize(-30, 20);
+        assert_eq!(unwrap_f!(window.get_framebuffer()).get_size(), (30, 20));
+        window.inject_resize(40, 10);
+        window.fetch_events();
+        let framebuffer = unwrap_f!(window.get_framebuffer());
+        assert_eq!(framebuffer.get_size(), (40, 10));
+        assert!(framebuffer.get_pixels().iter().all(|&p| p == 0));
+        let aspects = window.get_event_engine().get_window_aspects();
+        assert_eq!((aspects.get_width(), aspects.get_height()), (40, 10));
+    }
+
+    #[test]
+    fn injected_inputs_reach_the_listeners() {
+        let window = new_window(30, 20);
+        let engine = window.get_event_engine();
+        assert_eq!(engine.drain().count(), 0);
+        window.inject_key_press(Keyboard::A);
+        window.inject_mouse_move(4, 5);
+        window.inject_wheel(0.0, 1.0);
+        window.inject_touch_down(1, 2, 0);
+        window.inject_touch_up(1, 2, 0);
+        window.inject_gamepad_connect(3, "pad");
+        window.inject_pen(PenState {
+            position: (6, 7),
+            pressure: 0.5,
+            tilt: (0.0, 0.0),
+            rotation: 0.0,
+            button: None,
+            eraser: false,
+            proximity: true,
+        });
+        window.inject_focus();
+        window.fetch_events();
+        let state = engine.input_state();
+        assert!(state.is_pressed(&Button::Keyboard(Keyboard::A)));
+        assert_eq!(state.get_pointer_position(), (4, 5));
+        assert!(state.get_gamepads().contains_key(&3));
+        let events = engine.drain().collect::<Vec<Event>>();
+        let count = |f: fn(&Data) -> bool| events.iter().filter(|e| f(e.get_data())).count();
+        assert_eq!(
+            count(|d| matches!(
+                d,
+                Data::Button {
+                    button: Button::Keyboard(Keyboard::A),
+                    action: ButtonAction::Press,
+                }
+            )),
+            1
+        );
+        assert_eq!(
+            count(|d| matches!(
+                d,
+                Data::Move(Move::Mouse {
+                    current: (4, 5),
+                    ..
+                })
+            )),
+            1
+        );
+        assert_eq!(
+            count(|d| matches!(d, Data::Move(Move::Wheel { delta }) if *delta == (0.0, 1.0))),
+            1
+        );
+        assert_eq!(count(|d| matches!(d, Data::Touch(Touch::Raw { .. }))), 2);
+        assert_eq!(
+            count(|d| matches!(d, Data::Gamepad(Gamepad::Connected { id: 3, .. }))),
+            1
+        );
+        assert_eq!(
+            count(|d| matches!(
+                d,
+                Data::Pen {
+                    position: (6, 7),
+                    ..
+                }
+            )),
+            1
+        );
+        assert_eq!(
+            count(|d| matches!(d, Data::Window(crate::event::Window::Focus))),
+            1
+        );
+    }
+}
@@ -0,0 +1,842 @@
+use {
+    super::event::{
+        ActionState, Button, ButtonAction, Data, Engine, Event, EventCategory, EventSender,
+        Gamepad, GamepadAxis, GamepadButton, GamepadId, Keyboard, Listener, ListenerHandle, Mouse,
+        Move,
+    },
+    bitflags::bitflags,
+    log::result_f,
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        fmt,
+        fs::File,
+        io::{self, Read},
+        path::Path,
+        str::FromStr,
+        sync::{Arc, RwLock},
+    },
+};
+
+bitflags! {
+    /// Modifiers match both of the left and right keys.
+    #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+    #[cfg_attr(feature = "debug-derive", derive(Debug))]
+    pub struct Modifiers: u8 {
+        const SHIFT = 1;
+        const CONTROL = 2;
+        const ALT = 4;
+        const SUPER = 8;
+    }
+}
+
+impl Modifiers {
+    fn from_button(b: &Button) -> Self {
+        match b {
+            &Button::Keyboard(Keyboard::Shift(_)) => Self::SHIFT,
+            &Button::Keyboard(Keyboard::Control(_)) => Self::CONTROL,
+            &Button::Keyboard(Keyboard::Alt(_)) => Self::ALT,
+            &Button::Keyboard(Keyboard::Super(_)) | &Button::Keyboard(Keyboard::Command(_)) => {
+                Self::SUPER
+            }
+            _ => Self::empty(),
+        }
+    }
+
+    fn parse_name(s: &str) -> Option<Self> {
+        Some(match s {
+            "Shift" => Self::SHIFT,
+            "Control" | "Ctrl" => Self::CONTROL,
+            "Alt" => Self::ALT,
+            "Super" | "Command" => Self::SUPER,
+            _ => return None,
+        })
+    }
+}
+
+/// A button that must be pressed while exactly the `modifiers` are held, so
+/// `S` is not active while `Control+S` is.
+#[derive(Clone, PartialEq)]
+#[cfg_attr(feature = "debug-derive", derive(Debug))]
+pub struct Binding {
+    pub button: Button,
+    pub modifiers: Modifiers,
+}
+
+impl Binding {
+    pub fn new(button: Button) -> Self {
+        Self {
+            button,
+            modifiers: Modifiers::empty(),
+        }
+    }
+
+    pub fn with_modifiers(button: Button, modifiers: Modifiers) -> Self {
+        Self { button, modifiers }
+    }
+
+    /// The modifier of the binding's own button is ignored, e.g. for `Shift`.
+    fn is_active(&self, pressed: &BTreeSet<Button>, modifiers: Modifiers) -> bool {
+        let own = Modifiers::from_button(&self.button);
+        pressed.contains(&self.button) && modifiers - own == self.modifiers - own
+    }
+
+    /// Extra modifiers are allowed, so an axis keeps moving while e.g. the
+    /// Shift is held for running.
+    fn is_held(&self, pressed: &BTreeSet<Button>, modifiers: Modifiers) -> bool {
+        pressed.contains(&self.button) && modifiers.contains(self.modifiers)
+    }
+}
+
+impl FromStr for Binding {
+    type Err = String;
+
+    /// Format is the modifiers and the button joined with `+`, e.g. `Control+Shift+S`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split('+').map(|p| p.trim()).collect();
+        let (button, modifiers) = match parts.split_last() {
+            Some((button, modifiers)) => (button, modifiers),
+            None => return Err("Empty binding".to_string()),
+        };
+        let mut result = Self::new(button.parse()?);
+        for m in modifiers {
+            match Modifiers::parse_name(m) {
+                Some(m) => result.modifiers |= m,
+                None => return Err(format!("Unknown modifier: {}", m)),
+            }
+        }
+        Ok(result)
+    }
+}
+
+#[derive(Clone, PartialEq)]
+#[cfg_attr(feature = "debug-derive", derive(Debug))]
+pub enum AxisBinding {
+    /// Value is 1 when the positive is active, -1 when the negative is active
+    /// and 0 when both or none of them are active.
+    Buttons {
+        positive: Binding,
+        negative: Binding,
+    },
+    /// Value is the normalized horizontal delta of the mouse multiplied by the scale.
+    MouseX { scale: f64 },
+    /// Value is the normalized vertical delta of the mouse multiplied by the scale.
+    MouseY { scale: f64 },
+    /// Value is the position of the gamepad's axis multiplied by the scale.
+    Gamepad {
+        id: GamepadId,
+        axis: GamepadAxis,
+        scale: f64,
+    },
+}
+
+impl FromStr for AxisBinding {
+    type Err = String;
+
+    /// Format is either `<positive> / <negative>` or
+    /// `MouseX|MouseY|Gamepad.<axis>[.<id>] [* scale]`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut pn = s.splitn(2, '/');
+        let first = pn.next().unwrap_or("").trim();
+        if let Some(negative) = pn.next() {
+            return Ok(AxisBinding::Buttons {
+                positive: first.parse()?,
+                negative: negative.trim().parse()?,
+            });
+        }
+        let mut ms = first.splitn(2, '*');
+        let source = ms.next().unwrap_or("").trim();
+        let scale = match ms.next() {
+            Some(scale) => match scale.trim().parse() {
+                Ok(scale) => scale,
+                Err(_) => return Err(format!("Invalid axis scale: {}", scale)),
+            },
+            None => 1.0,
+        };
+        match source {
+            "MouseX" => Ok(AxisBinding::MouseX { scale }),
+            "MouseY" => Ok(AxisBinding::MouseY { scale }),
+            _ if source.starts_with("Gamepad.") => {
+                let (name, id) = split_gamepad_id(&source[8..])?;
+                let axis = match name {
+                    "LeftX" => GamepadAxis::LeftX,
+                    "LeftY" => GamepadAxis::LeftY,
+                    "RightX" => GamepadAxis::RightX,
+                    "RightY" => GamepadAxis::RightY,
+                    "LeftTrigger" => GamepadAxis::LeftTrigger,
+                    "RightTrigger" => GamepadAxis::RightTrigger,
+                    _ => return Err(format!("Unknown gamepad axis: {}", s)),
+                };
+                Ok(AxisBinding::Gamepad { id, axis, scale })
+            }
+            _ => Err(format!("Unknown axis: {}", s)),
+        }
+    }
+}
+
+/// Splits the optional `.<id>` suffix of a gamepad's button or axis, the
+/// default id is 0.
+fn split_gamepad_id(s: &str) -> Result<(&str, GamepadId), String> {
+    let mut ni = s.splitn(2, '.');
+    let name = ni.next().unwrap_or("");
+    match ni.next() {
+        Some(i) => match i.parse() {
+            Ok(i) => Ok((name, i)),
+            Err(_) => Err(format!("Invalid gamepad id: {}", s)),
+        },
+        None => Ok((name, 0)),
+    }
+}
+
+impl FromStr for Button {
+    type Err = String;
+
+    /// Names are the variant names, indexed variants accept a `.<index>`
+    /// suffix (e.g. `Shift.1`), digits are `0`-`9`, keypad digits are
+    /// `Pad0`-`Pad9`, mouse buttons are prefixed by `Mouse.` and gamepad
+    /// buttons are prefixed by `Gamepad.` with an optional `.<id>` suffix
+    /// (e.g. `Gamepad.South.1`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(gamepad) = s.strip_prefix("Gamepad.") {
+            let (name, id) = split_gamepad_id(gamepad)?;
+            return Ok(Button::Gamepad {
+                id,
+                button: match name {
+                    "South" => GamepadButton::South,
+                    "East" => GamepadButton::East,
+                    "West" => GamepadButton::West,
+                    "North" => GamepadButton::North,
+                    "LeftBumper" => GamepadButton::LeftBumper,
+                    "RightBumper" => GamepadButton::RightBumper,
+                    "LeftTrigger" => GamepadButton::LeftTrigger,
+                    "RightTrigger" => GamepadButton::RightTrigger,
+                    "Select" => GamepadButton::Select,
+                    "Start" => GamepadButton::Start,
+                    "Mode" => GamepadButton::Mode,
+                    "LeftThumb" => GamepadButton::LeftThumb,
+                    "RightThumb" => GamepadButton::RightThumb,
+                    "DPadUp" => GamepadButton::DPadUp,
+                    "DPadDown" => GamepadButton::DPadDown,
+                    "DPadLeft" => GamepadButton::DPadLeft,
+                    "DPadRight" => GamepadButton::DPadRight,
+                    _ => return Err(format!("Unknown gamepad button: {}", s)),
+                },
+            });
+        }
+        if let Some(mouse) = s.strip_prefix("Mouse.") {
+            return Ok(Button::Mouse(match mouse {
+                "Left" => Mouse::Left,
+                "Right" => Mouse::Right,
+                "Middle" => Mouse::Middle,
+                "Back" => Mouse::Back,
+                "Forward" => Mouse::Forward,
+                "Office" => Mouse::Office,
+                _ => return Err(format!("Unknown mouse button: {}", s)),
+            }));
+        }
+        let mut ni = s.splitn(2, '.');
+        let name = ni.next().unwrap_or("");
+        let index: u8 = match ni.next() {
+            Some(i) => match i.parse() {
+                Ok(i) => i,
+                Err(_) => return Err(format!("Invalid key index: {}", s)),
+            },
+            None => 0,
+        };
+        if name.len() == 1 {
+            let c = name.as_bytes()[0];
+            if c.is_ascii_digit() {
+                return Ok(Button::Keyboard(Keyboard::Number {
+                    number: (c - b'0') as i32,
+                    pad: false,
+                }));
+            }
+        }
+        if name.len() == 4 && name.starts_with("Pad") {
+            let c = name.as_bytes()[3];
+            if c.is_ascii_digit() {
+                return Ok(Button::Keyboard(Keyboard::Number {
+                    number: (c - b'0') as i32,
+                    pad: true,
+                }));
+            }
+        }
+        if name.len() > 1 && name.starts_with('F') {
+            if let Ok(f) = name[1..].parse() {
+                return Ok(Button::Keyboard(Keyboard::Function(f)));
+            }
+        }
+        Ok(Button::Keyboard(match name {
+            "A" => Keyboard::A,
+            "B" => Keyboard::B,
+            "C" => Keyboard::C,
+            "D" => Keyboard::D,
+            "E" => Keyboard::E,
+            "F" => Keyboard::F,
+            "G" => Keyboard::G,
+            "H" => Keyboard::H,
+            "I" => Keyboard::I,
+            "J" => Keyboard::J,
+            "K" => Keyboard::K,
+            "L" => Keyboard::L,
+            "M" => Keyboard::M,
+            "N" => Keyboard::N,
+            "O" => Keyboard::O,
+            "P" => Keyboard::P,
+            "Q" => Keyboard::Q,
+            "R" => Keyboard::R,
+            "S" => Keyboard::S,
+            "T" => Keyboard::T,
+            "U" => Keyboard::U,
+            "V" => Keyboard::V,
+            "W" => Keyboard::W,
+            "X" => Keyboard::X,
+            "Y" => Keyboard::Y,
+            "Z" => Keyboard::Z,
+            "Escape" => Keyboard::Escape(index),
+            "PrintScreen" => Keyboard::PrintScreen,
+            "ScrollLock" => Keyboard::ScrollLock,
+            "PauseBreak" => Keyboard::PauseBreak,
+            "BackQuote" => Keyboard::BackQuote,
+            "Backspace" => Keyboard::Backspace,
+            "Delete" => Keyboard::Delete,
+            "Insert" => Keyboard::Insert,
+            "Home" => Keyboard::Home,
+            "End" => Keyboard::End,
+            "PageUp" => Keyboard::PageUp,
+            "PageDown" => Keyboard::PageDown,
+            "NumLock" => Keyboard::NumLock,
+            "Slash" => Keyboard::Slash(index),
+            "Star" => Keyboard::Star,
+            "Plus" => Keyboard::Plus(index),
+            "Minus" => Keyboard::Minus(index),
+            "Enter" => Keyboard::Enter(index),
+            "Dot" => Keyboard::Dot(index),
+            "Tab" => Keyboard::Tab,
+            "BracketLeft" => Keyboard::BracketLeft,
+            "BracketRight" => Keyboard::BracketRight,
+            "CapsLock" => Keyboard::CapsLock(index),
+            "SemiColon" => Keyboard::SemiColon,
+            "Quote" => Keyboard::Quote,
+            "BackSlash" => Keyboard::BackSlash(index),
+            "Shift" => Keyboard::Shift(index),
+            "Comma" => Keyboard::Comma(index),
+            "Control" => Keyboard::Control(index),
+            "Alt" => Keyboard::Alt(index),
+            "Space" => Keyboard::Space(index),
+            "Command" => Keyboard::Command(index),
+            "Super" => Keyboard::Super(index),
+            "Properties" => Keyboard::Properties(index),
+            "ArrowUp" => Keyboard::ArrowUp,
+            "ArrowDown" => Keyboard::ArrowDown,
+            "ArrowLeft" => Keyboard::ArrowLeft,
+            "ArrowRight" => Keyboard::ArrowRight,
+            "Equal" => Keyboard::Equal,
+            "Menu" => Keyboard::Menu(index),
+            "Back" => Keyboard::Back,
+            "VolumeUp" => Keyboard::VolumeUp,
+            "VolumeDown" => Keyboard::VolumeDown,
+            "VolumeMute" => Keyboard::VolumeMute,
+            "MediaPlayPause" => Keyboard::MediaPlayPause,
+            "MediaPlay" => Keyboard::MediaPlay,
+            "MediaPause" => Keyboard::MediaPause,
+            "MediaStop" => Keyboard::MediaStop,
+            "MediaNext" => Keyboard::MediaNext,
+            "MediaPrevious" => Keyboard::MediaPrevious,
+            "MediaRewind" => Keyboard::MediaRewind,
+            "MediaFastForward" => Keyboard::MediaFastForward,
+            _ => return Err(format!("Unknown button: {}", s)),
+        }))
+    }
+}
+
+#[cfg_attr(feature = "debug-derive", derive(Debug))]
+pub enum Error {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "Can not read the action map: {}", e),
+            Error::Parse { line, message } => {
+                write!(f, "Action map error at line {}: {}", line, message)
+            }
+        }
+    }
+}
+
+/// A listener that turns buttons, mouse movements and gamepad axes into the
+/// `Data::Action` events of the named actions and axes.
+///
+/// Bindings can be changed at any time and also can be loaded from a file
+/// with lines like:
+/// ```text
+/// # comment
+/// action jump = Space, Mouse.Right, Gamepad.South
+/// action save = Control+S
+/// axis move_x = D / A, ArrowRight / ArrowLeft, Gamepad.LeftX
+/// axis look_x = MouseX * 0.5
+/// ```
+pub struct ActionMap {
+    sender: EventSender,
+    actions: BTreeMap<String, Vec<Binding>>,
+    axes: BTreeMap<String, Vec<AxisBinding>>,
+    pressed: BTreeSet<Button>,
+    gamepad_axes: BTreeMap<(GamepadId, GamepadAxis), f64>,
+    active_actions: BTreeSet<String>,
+    axes_values: BTreeMap<String, f64>,
+    handle: Option<ListenerHandle>,
+}
+
+impl ActionMap {
+    /// Created map is registered in the engine with the `priority`, it is
+    /// removed from the engine when the returned value is dropped.
+    pub fn new(engine: &Engine, priority: i64) -> Arc<RwLock<Self>> {
+        let result = Arc::new(RwLock::new(Self {
+            sender: engine.get_sender(),
+            actions: BTreeMap::new(),
+            axes: BTreeMap::new(),
+            pressed: BTreeSet::new(),
+            gamepad_axes: BTreeMap::new(),
+            active_actions: BTreeSet::new(),
+            axes_values: BTreeMap::new(),
+            handle: None,
+        }));
+        let l: Arc<RwLock<dyn Listener>> = result.clone();
+        result_f!(result.write()).handle = Some(engine.add(
+            priority,
+            EventCategory::KEYBOARD | EventCategory::MOUSE | EventCategory::GAMEPAD,
+            Arc::downgrade(&l),
+        ));
+        result
+    }
+
+    /// Changes the priority of the map among the engine's listeners.
+    pub fn set_priority(&mut self, priority: i64) {
+        if let Some(ref mut h) = self.handle {
+            h.set_priority(priority);
+        }
+    }
+
+    pub fn bind(&mut self, name: &str, binding: Binding) {
+        self.actions
+            .entry(name.to_string())
+            .or_default()
+            .push(binding);
+    }
+
+    pub fn bind_axis(&mut self, name: &str, binding: AxisBinding) {
+        self.axes.entry(name.to_string()).or_default().push(binding);
+    }
+
+    /// Removes all of the bindings of the action or axis.
+    pub fn unbind(&mut self, name: &str) {
+        self.actions.remove(name);
+        self.axes.remove(name);
+        self.active_actions.remove(name);
+        self.axes_values.remove(name);
+    }
+
+    pub fn clear(&mut self) {
+        self.actions.clear();
+        self.axes.clear();
+        self.active_actions.clear();
+        self.axes_values.clear();
+    }
+
+    pub fn get_bindings(&self, name: &str) -> Option<&Vec<Binding>> {
+        self.actions.get(name)
+    }
+
+    pub fn get_axis_bindings(&self, name: &str) -> Option<&Vec<AxisBinding>> {
+        self.axes.get(name)
+    }
+
+    pub fn is_active(&self, name: &str) -> bool {
+        self.active_actions.contains(name)
+    }
+
+    pub fn get_axis_value(&self, name: &str) -> f64 {
+        *self.axes_values.get(name).unwrap_or(&0.0)
+    }
+
+    /// Bindings of the file are added to the current ones.
+    pub fn load<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
+        let mut s = String::new();
+        File::open(path)
+            .and_then(|mut f| f.read_to_string(&mut s))
+            .map_err(Error::Io)?;
+        self.parse(&s)
+    }
+
+    pub fn parse(&mut self, s: &str) -> Result<(), Error> {
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = |message: String| Error::Parse {
+                line: i + 1,
+                message,
+            };
+            let mut kv = line.splitn(2, '=');
+            let mut key = kv.next().unwrap_or("").split_whitespace();
+            let bindings = match kv.next() {
+                Some(b) => b,
+                None => return Err(err("Missing '='".to_string())),
+            };
+            let (kind, name) = match (key.next(), key.next(), key.next()) {
+                (Some(kind), Some(name), None) => (kind, name),
+                _ => return Err(err("Expected '<action|axis> <name> = ...'".to_string())),
+            };
+            for b in bindings.split(',') {
+                match kind {
+                    "action" => self.bind(name, b.parse().map_err(&err)?),
+                    "axis" => self.bind_axis(name, b.parse().map_err(&err)?),
+                    _ => return Err(err(format!("Unknown kind: {}", kind))),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn emit(&self, name: &str, state: ActionState, value: f64) {
+        self.sender.send(Event::new(Data::Action {
+            name: name.to_string(),
+            state,
+            value,
+        }));
+    }
+
+    fn get_modifiers(&self) -> Modifiers {
+        self.pressed
+            .iter()
+            .fold(Modifiers::empty(), |m, b| m | Modifiers::from_button(b))
+    }
+
+    fn update_buttons(&mut self) {
+        let modifiers = self.get_modifiers();
+        for (name, bindings) in &self.actions {
+            let active = bindings
+                .iter()
+                .any(|b| b.is_active(&self.pressed, modifiers));
+            if active == self.active_actions.contains(name) {
+                continue;
+            }
+            if active {
+                self.active_actions.insert(name.clone());
+                self.emit(name, ActionState::Pressed, 1.0);
+            } else {
+                self.active_actions.remove(name);
+                self.emit(name, ActionState::Released, 0.0);
+            }
+        }
+        self.update_axes(modifiers);
+    }
+
+    /// Updates the axes that have a state, i.e. the buttons and gamepads.
+    fn update_axes(&mut self, modifiers: Modifiers) {
+        for (name, bindings) in &self.axes {
+            let mut value: f64 = 0.0;
+            let mut has_state = false;
+            for b in bindings {
+                match b {
+                    AxisBinding::Buttons { positive, negative } => {
+                        has_state = true;
+                        if positive.is_held(&self.pressed, modifiers) {
+                            value += 1.0;
+                        }
+                        if negative.is_held(&self.pressed, modifiers) {
+                            value -= 1.0;
+                        }
+                    }
+                    AxisBinding::Gamepad { id, axis, scale } => {
+                        has_state = true;
+                        value +=
+                            self.gamepad_axes.get(&(*id, *axis)).cloned().unwrap_or(0.0) * scale;
+                    }
+                    _ => (),
+                }
+            }
+            if !has_state {
+                continue;
+            }
+            let value = value.clamp(-1.0, 1.0);
+            if value != self.get_axis_value(name) {
+                self.axes_values.insert(name.clone(), value);
+                self.emit(name, ActionState::Changed, value);
+            }
+        }
+    }
+
+    fn update_mouse(&mut self, delta: (f64, f64)) {
+        for (name, bindings) in &self.axes {
+            let mut value = 0.0;
+            let mut has_mouse = false;
+            for b in bindings {
+                match b {
+                    AxisBinding::MouseX { scale } => {
+                        has_mouse = true;
+                        value += delta.0 * scale;
+                    }
+                    AxisBinding::MouseY { scale } => {
+                        has_mouse = true;
+                        value += delta.1 * scale;
+                    }
+                    _ => (),
+                }
+            }
+            if has_mouse && value != 0.0 {
+                self.emit(name, ActionState::Changed, value);
+            }
+        }
+    }
+}
+
+impl Listener for ActionMap {
+    fn on_event(&mut self, e: &Event) -> bool {
+        match e.get_data() {
+            Data::Button { button, action } => {
+                match action {
+                    ButtonAction::Press => self.pressed.insert(button.clone()),
+                    ButtonAction::Release => self.pressed.remove(button),
+                };
+                self.update_buttons();
+            }
+            Data::Move(Move::Mouse {
+                normalized_delta, ..
+            }) => self.update_mouse(*normalized_delta),
+            Data::Move(Move::Gamepad {
+                id, axis, current, ..
+            }) => {
+                self.gamepad_axes.insert((*id, *axis), *current);
+                let modifiers = self.get_modifiers();
+                self.update_axes(modifiers);
+            }
+            Data::Gamepad(Gamepad::Disconnected { id }) => {
+                self.gamepad_axes.retain(|&(i, _), _| i != *id);
+                let modifiers = self.get_modifiers();
+                self.update_axes(modifiers);
+            }
+            _ => (),
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::event::DispatchMode;
+    use super::*;
+
+    fn key(k: Keyboard) -> Button {
+        Button::Keyboard(k)
+    }
+
+    fn new_map(bindings: &str) -> (Engine, Arc<RwLock<ActionMap>>) {
+        let engine = Engine::new();
+        engine.set_dispatch_mode(DispatchMode::CallerThread);
+        engine.init_window_aspects(200, 100, 1.0);
+        let map = ActionMap::new(&engine, 0);
+        assert!(result_f!(map.write()).parse(bindings).is_ok());
+        (engine, map)
+    }
+
+    fn actions(receiver: &super::super::event::EventReceiver) -> Vec<(String, f64)> {
+        receiver
+            .drain()
+            .filter_map(|e| match e.get_data() {
+                &Data::Action {
+                    ref name, value, ..
+                } => Some((name.clone(), value)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn buttons_are_parsed() {
+        let parse = |s: &str| s.parse::<Button>();
+        assert!(parse("A") == Ok(key(Keyboard::A)));
+        assert!(parse(" Shift.1 ") == Ok(key(Keyboard::Shift(1))));
+        assert!(parse("Shift") == Ok(key(Keyboard::Shift(0))));
+        assert!(
+            parse("7")
+                == Ok(key(Keyboard::Number {
+                    number: 7,
+                    pad: false
+                }))
+        );
+        assert!(
+            parse("Pad3")
+                == Ok(key(Keyboard::Number {
+                    number: 3,
+                    pad: true
+                }))
+        );
+        assert!(parse("F11") == Ok(key(Keyboard::Function(11))));
+        assert!(parse("F") == Ok(key(Keyboard::F)));
+        assert!(parse("Mouse.Right") == Ok(Button::Mouse(Mouse::Right)));
+        assert!(
+            parse("Gamepad.South.2")
+                == Ok(Button::Gamepad {
+                    id: 2,
+                    button: GamepadButton::South
+                })
+        );
+        assert!(
+            parse("Gamepad.DPadUp")
+                == Ok(Button::Gamepad {
+                    id: 0,
+                    button: GamepadButton::DPadUp
+                })
+        );
+        for s in &[
+            "",
+            "Nope",
+            "Shift.x",
+            "Mouse.Nope",
+            "Gamepad.Nope",
+            "Gamepad.South.x",
+        ] {
+            assert!(parse(s).is_err(), "{} must not be parsed", s);
+        }
+    }
+
+    #[test]
+    fn bindings_are_parsed() {
+        let b: Binding = result_f!("Control + Shift+S".parse());
+        assert!(b.button == key(Keyboard::S));
+        assert!(b.modifiers == Modifiers::CONTROL | Modifiers::SHIFT);
+        let b: Binding = result_f!("Ctrl+Command+Alt+Mouse.Left".parse());
+        assert!(b.modifiers == Modifiers::CONTROL | Modifiers::SUPER | Modifiers::ALT);
+        assert!("Hyper+S".parse::<Binding>().is_err());
+        assert!("Control+".parse::<Binding>().is_err());
+        assert!("Control+Nope".parse::<Binding>().is_err());
+    }
+
+    #[test]
+    fn axis_bindings_are_parsed() {
+        assert!(
+            "D / Shift+A".parse::<AxisBinding>()
+                == Ok(AxisBinding::Buttons {
+                    positive: Binding::new(key(Keyboard::D)),
+                    negative: Binding::with_modifiers(key(Keyboard::A), Modifiers::SHIFT),
+                })
+        );
+        assert!("MouseX * 0.5".parse::<AxisBinding>() == Ok(AxisBinding::MouseX { scale: 0.5 }));
+        assert!("MouseY".parse::<AxisBinding>() == Ok(AxisBinding::MouseY { scale: 1.0 }));
+        assert!(
+            "Gamepad.LeftX.1 * -1".parse::<AxisBinding>()
+                == Ok(AxisBinding::Gamepad {
+                    id: 1,
+                    axis: GamepadAxis::LeftX,
+                    scale: -1.0,
+                })
+        );
+        for s in &[
+            "MouseX * x",
+            "Gamepad.Nope",
+            "Gamepad.LeftX.x",
+            "Wheel",
+            "D / Nope",
+        ] {
+            assert!(
+                s.parse::<AxisBinding>().is_err(),
+                "{} must not be parsed",
+                s
+            );
+        }
+    }
+
+    #[test]
+    fn map_errors_have_line_numbers() {
+        let (_engine, map) = new_map("");
+        let mut map = result_f!(map.write());
+        let line = |r: Result<(), Error>| match r {
+            Err(Error::Parse { line, .. }) => line,
+            _ => 0,
+        };
+        assert_eq!(line(map.parse("# comment\n\naction jump Space")), 3);
+        assert_eq!(line(map.parse("action jump = Space\nbutton x = A")), 2);
+        assert_eq!(line(map.parse("axis = A")), 1);
+        assert_eq!(line(map.parse("axis x = Nope")), 1);
+    }
+
+    #[test]
+    fn actions_are_pressed_and_released() {
+        let (engine, map) = new_map("action jump = Space, Mouse.Right\naction sprint = Shift");
+        let receiver = engine.subscribe();
+        engine.button_pressed(key(Keyboard::Space(0)));
+        engine.button_pressed(Button::Mouse(Mouse::Right));
+        engine.dispatch();
+        assert!(result_f!(map.read()).is_active("jump"));
+        engine.button_released(key(Keyboard::Space(0)));
+        engine.dispatch();
+        assert!(result_f!(map.read()).is_active("jump"));
+        engine.button_released(Button::Mouse(Mouse::Right));
+        engine.dispatch();
+        assert!(!result_f!(map.read()).is_active("jump"));
+        engine.button_pressed(key(Keyboard::Shift(0)));
+        engine.dispatch();
+        assert!(result_f!(map.read()).is_active("sprint"));
+        engine.dispatch();
+        assert_eq!(
+            actions(&receiver),
+            vec![
+                ("jump".to_string(), 1.0),
+                ("jump".to_string(), 0.0),
+                ("sprint".to_string(), 1.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn modifiers_must_match_exactly() {
+        let (engine, map) = new_map("action save = Control+S\naction south = S");
+        engine.button_pressed(key(Keyboard::Control(0)));
+        engine.button_pressed(key(Keyboard::S));
+        engine.dispatch();
+        assert!(result_f!(map.read()).is_active("save"));
+        assert!(!result_f!(map.read()).is_active("south"));
+        engine.button_released(key(Keyboard::Control(0)));
+        engine.dispatch();
+        assert!(!result_f!(map.read()).is_active("save"));
+        assert!(result_f!(map.read()).is_active("south"));
+    }
+
+    #[test]
+    fn axes_follow_buttons_gamepads_and_the_mouse() {
+        let (engine, map) =
+            new_map("axis move_x = D / A, Gamepad.LeftX * 0.5\naxis look_x = MouseX * 2");
+        let receiver = engine.subscribe();
+        engine.button_pressed(key(Keyboard::Shift(0)));
+        engine.button_pressed(key(Keyboard::D));
+        engine.dispatch();
+        assert_eq!(result_f!(map.read()).get_axis_value("move_x"), 1.0);
+        engine.button_pressed(key(Keyboard::A));
+        engine.dispatch();
+        assert_eq!(result_f!(map.read()).get_axis_value("move_x"), 0.0);
+        engine.button_released(key(Keyboard::D));
+        engine.button_released(key(Keyboard::A));
+        engine.gamepad_connected(0, "pad".to_string());
+        engine.gamepad_axis_changed(0, GamepadAxis::LeftX, -1.0);
+        engine.dispatch();
+        assert_eq!(result_f!(map.read()).get_axis_value("move_x"), -0.5);
+        engine.gamepad_disconnected(0);
+        engine.dispatch();
+        assert_eq!(result_f!(map.read()).get_axis_value("move_x"), 0.0);
+        engine.init_mouse_position((0, 0));
+        engine.set_mouse_position((50, 0));
+        engine.dispatch();
+        engine.dispatch();
+        let looks: Vec<f64> = actions(&receiver)
+            .into_iter()
+            .filter(|a| a.0 == "look_x")
+            .map(|a| a.1)
+            .collect();
+        assert_eq!(looks, vec![2.0]);
+    }
+}
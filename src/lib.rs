@@ -1,30 +1,45 @@
-#![feature(drain_filter)]
-
+extern crate arc_swap;
 pub extern crate bitflags;
+extern crate crossbeam_queue;
 pub extern crate libc;
 pub extern crate rust_graphics_log as log;
+#[cfg(feature = "serde")]
+pub extern crate serde;
+#[cfg(feature = "serde")]
+pub extern crate serde_json;
+#[cfg(target_arch = "wasm32")]
+pub extern crate web_time;
 
 #[cfg(not(target_arch = "wasm32"))]
 pub extern crate rust_graphics_library_loader as library_loader;
 
 pub mod event;
+mod gesture;
+pub mod input;
+#[cfg(feature = "serde")]
+pub mod record;
+
+#[cfg(feature = "headless")]
+pub mod headless;
+#[cfg(feature = "headless")]
+pub use headless::window::*;
 
-#[cfg(target_os = "android")]
+#[cfg(all(target_os = "android", not(feature = "headless")))]
 pub mod android;
-#[cfg(target_os = "android")]
+#[cfg(all(target_os = "android", not(feature = "headless")))]
 pub use android::window::*;
 
-#[cfg(target_os = "linux")]
+#[cfg(all(target_os = "linux", not(feature = "headless")))]
 pub mod linux;
-#[cfg(target_os = "linux")]
+#[cfg(all(target_os = "linux", not(feature = "headless")))]
 pub use linux::window::*;
 
-#[cfg(target_os = "windows")]
+#[cfg(all(target_os = "windows", not(feature = "headless")))]
 pub mod windows;
-#[cfg(target_os = "windows")]
+#[cfg(all(target_os = "windows", not(feature = "headless")))]
 pub use windows::window::*;
 
-#[cfg(target_arch = "wasm32")]
+#[cfg(all(target_arch = "wasm32", not(feature = "headless")))]
 pub mod wasm;
-#[cfg(target_arch = "wasm32")]
+#[cfg(all(target_arch = "wasm32", not(feature = "headless")))]
 pub use wasm::window::*;
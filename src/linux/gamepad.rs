@@ -0,0 +1,930 @@
+use {
+    crate::event::{Button, Engine, GamepadAxis, GamepadButton, GamepadId},
+    log::{log_e, log_i},
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        env,
+        fs::{self, File, OpenOptions},
+        io::{self, ErrorKind, Read},
+        mem::{size_of, zeroed},
+        os::{
+            raw::c_void,
+            unix::{fs::OpenOptionsExt, io::AsRawFd},
+        },
+        path::{Path, PathBuf},
+        slice,
+        time::{Duration, Instant},
+    },
+};
+
+const EV_KEY: u16 = 0x01;
+const EV_ABS: u16 = 0x03;
+const EV_MAX: usize = 0x1f;
+const KEY_MAX: usize = 0x2ff;
+const ABS_MAX: usize = 0x3f;
+
+const BTN_JOYSTICK: usize = 0x120;
+const BTN_SOUTH: u16 = 0x130;
+const BTN_EAST: u16 = 0x131;
+const BTN_NORTH: u16 = 0x133;
+const BTN_WEST: u16 = 0x134;
+const BTN_TL: u16 = 0x136;
+const BTN_TR: u16 = 0x137;
+const BTN_TL2: u16 = 0x138;
+const BTN_TR2: u16 = 0x139;
+const BTN_SELECT: u16 = 0x13a;
+const BTN_START: u16 = 0x13b;
+const BTN_MODE: u16 = 0x13c;
+const BTN_THUMBL: u16 = 0x13d;
+const BTN_THUMBR: u16 = 0x13e;
+const BTN_DPAD_UP: u16 = 0x220;
+const BTN_DPAD_DOWN: u16 = 0x221;
+const BTN_DPAD_LEFT: u16 = 0x222;
+const BTN_DPAD_RIGHT: u16 = 0x223;
+
+const ABS_X: u16 = 0x00;
+const ABS_Y: u16 = 0x01;
+const ABS_Z: u16 = 0x02;
+const ABS_RX: u16 = 0x03;
+const ABS_RY: u16 = 0x04;
+const ABS_RZ: u16 = 0x05;
+const ABS_HAT0X: u16 = 0x10;
+const ABS_HAT0Y: u16 = 0x11;
+const ABS_HAT3Y: u16 = 0x17;
+
+/// Devices are looked up this often for the hot-plugging.
+const SCAN_INTERVAL: Duration = Duration::from_secs(1);
+const DEVICES_DIRECTORY: &str = "/dev/input";
+
+fn ioc_read(nr: usize, size: usize) -> libc::Ioctl {
+    ((2 << 30) | (size << 16) | ((b'E' as usize) << 8) | nr) as libc::Ioctl
+}
+
+fn ioctl<T>(file: &File, request: libc::Ioctl, data: *mut T) -> bool {
+    unsafe { libc::ioctl(file.as_raw_fd(), request, data as *mut c_void) >= 0 }
+}
+
+fn test_bit(bits: &[u8], n: usize) -> bool {
+    bits[n / 8] & (1 << (n % 8)) != 0
+}
+
+fn get_bits(file: &File, ev: usize, max: usize) -> Vec<u8> {
+    let mut bits = vec![0u8; max / 8 + 1];
+    if !ioctl(file, ioc_read(0x20 + ev, bits.len()), bits.as_mut_ptr()) {
+        bits.fill(0);
+    }
+    bits
+}
+
+#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "debug-derive", derive(Debug))]
+enum Range {
+    Full,
+    Positive,
+    Negative,
+}
+
+/// Inputs as they are indexed in the SDL's mappings.
+#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "debug-derive", derive(Debug))]
+enum Source {
+    Button(usize),
+    Axis {
+        index: usize,
+        range: Range,
+        invert: bool,
+    },
+    Hat {
+        index: usize,
+        mask: u8,
+    },
+}
+
+#[derive(Clone, PartialEq)]
+#[cfg_attr(feature = "debug-derive", derive(Debug))]
+enum Target {
+    Button(GamepadButton),
+    Axis(GamepadAxis, Range),
+}
+
+/// A line of the SDL's game controller database, e.g.
+/// `<guid>,<name>,a:b0,b:b1,leftx:a0,dpup:h0.1,...,platform:Linux,`.
+#[cfg_attr(feature = "debug-derive", derive(Debug))]
+struct Mapping {
+    guid: String,
+    name: String,
+    bindings: Vec<(Source, Target)>,
+}
+
+impl Mapping {
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let mut fields = line.split(',');
+        let guid = fields.next()?.trim().to_lowercase();
+        let name = fields.next()?.trim().to_string();
+        if guid.len() != 32 || !guid.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        let mut bindings = Vec::new();
+        for f in fields {
+            let mut kv = f.splitn(2, ':');
+            let key = kv.next().unwrap_or("").trim();
+            let value = match kv.next() {
+                Some(v) => v.trim(),
+                None => continue,
+            };
+            if key == "platform" {
+                if value != "Linux" {
+                    return None;
+                }
+                continue;
+            }
+            if let (Some(target), Some(source)) =
+                (Self::parse_target(key), Self::parse_source(value))
+            {
+                bindings.push((source, target));
+            }
+        }
+        Some(Self {
+            guid,
+            name,
+            bindings,
+        })
+    }
+
+    fn parse_target(key: &str) -> Option<Target> {
+        let (range, key) = if let Some(key) = key.strip_prefix('+') {
+            (Range::Positive, key)
+        } else if let Some(key) = key.strip_prefix('-') {
+            (Range::Negative, key)
+        } else {
+            (Range::Full, key)
+        };
+        let button = match key {
+            "a" => GamepadButton::South,
+            "b" => GamepadButton::East,
+            "x" => GamepadButton::West,
+            "y" => GamepadButton::North,
+            "back" => GamepadButton::Select,
+            "start" => GamepadButton::Start,
+            "guide" => GamepadButton::Mode,
+            "leftshoulder" => GamepadButton::LeftBumper,
+            "rightshoulder" => GamepadButton::RightBumper,
+            "leftstick" => GamepadButton::LeftThumb,
+            "rightstick" => GamepadButton::RightThumb,
+            "dpup" => GamepadButton::DPadUp,
+            "dpdown" => GamepadButton::DPadDown,
+            "dpleft" => GamepadButton::DPadLeft,
+            "dpright" => GamepadButton::DPadRight,
+            _ => {
+                let axis = match key {
+                    "leftx" => GamepadAxis::LeftX,
+                    "lefty" => GamepadAxis::LeftY,
+                    "rightx" => GamepadAxis::RightX,
+                    "righty" => GamepadAxis::RightY,
+                    "lefttrigger" => GamepadAxis::LeftTrigger,
+                    "righttrigger" => GamepadAxis::RightTrigger,
+                    _ => return None,
+                };
+                return Some(Target::Axis(axis, range));
+            }
+        };
+        Some(Target::Button(button))
+    }
+
+    fn parse_source(value: &str) -> Option<Source> {
+        let (range, value) = if let Some(value) = value.strip_prefix('+') {
+            (Range::Positive, value)
+        } else if let Some(value) = value.strip_prefix('-') {
+            (Range::Negative, value)
+        } else {
+            (Range::Full, value)
+        };
+        let (invert, value) = match value.strip_suffix('~') {
+            Some(value) => (true, value),
+            None => (false, value),
+        };
+        if value.len() < 2 {
+            return None;
+        }
+        let (kind, rest) = value.split_at(1);
+        match kind {
+            "b" => Some(Source::Button(rest.parse().ok()?)),
+            "a" => Some(Source::Axis {
+                index: rest.parse().ok()?,
+                range,
+                invert,
+            }),
+            "h" => {
+                let mut hm = rest.splitn(2, '.');
+                let index = hm.next()?.parse().ok()?;
+                let mask = hm.next()?.parse().ok()?;
+                Some(Source::Hat { index, mask })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Inputs of the device as evdev codes.
+#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "debug-derive", derive(Debug))]
+enum Input {
+    Key(u16),
+    Abs {
+        code: u16,
+        range: Range,
+        invert: bool,
+    },
+    Hat {
+        code: u16,
+        direction: i32,
+    },
+}
+
+impl Input {
+    fn get_code(&self) -> (u16, u16) {
+        match self {
+            &Input::Key(code) => (EV_KEY, code),
+            &Input::Abs { code, .. } | &Input::Hat { code, .. } => (EV_ABS, code),
+        }
+    }
+}
+
+fn default_bindings() -> Vec<(Input, Target)> {
+    let key = |code, b| (Input::Key(code), Target::Button(b));
+    let abs = |code, a| {
+        (
+            Input::Abs {
+                code,
+                range: Range::Full,
+                invert: false,
+            },
+            Target::Axis(a, Range::Full),
+        )
+    };
+    let hat = |code, direction, b| (Input::Hat { code, direction }, Target::Button(b));
+    vec![
+        key(BTN_SOUTH, GamepadButton::South),
+        key(BTN_EAST, GamepadButton::East),
+        key(BTN_NORTH, GamepadButton::North),
+        key(BTN_WEST, GamepadButton::West),
+        key(BTN_TL, GamepadButton::LeftBumper),
+        key(BTN_TR, GamepadButton::RightBumper),
+        key(BTN_TL2, GamepadButton::LeftTrigger),
+        key(BTN_TR2, GamepadButton::RightTrigger),
+        key(BTN_SELECT, GamepadButton::Select),
+        key(BTN_START, GamepadButton::Start),
+        key(BTN_MODE, GamepadButton::Mode),
+        key(BTN_THUMBL, GamepadButton::LeftThumb),
+        key(BTN_THUMBR, GamepadButton::RightThumb),
+        key(BTN_DPAD_UP, GamepadButton::DPadUp),
+        key(BTN_DPAD_DOWN, GamepadButton::DPadDown),
+        key(BTN_DPAD_LEFT, GamepadButton::DPadLeft),
+        key(BTN_DPAD_RIGHT, GamepadButton::DPadRight),
+        abs(ABS_X, GamepadAxis::LeftX),
+        abs(ABS_Y, GamepadAxis::LeftY),
+        abs(ABS_RX, GamepadAxis::RightX),
+        abs(ABS_RY, GamepadAxis::RightY),
+        abs(ABS_Z, GamepadAxis::LeftTrigger),
+        abs(ABS_RZ, GamepadAxis::RightTrigger),
+        hat(ABS_HAT0X, -1, GamepadButton::DPadLeft),
+        hat(ABS_HAT0X, 1, GamepadButton::DPadRight),
+        hat(ABS_HAT0Y, -1, GamepadButton::DPadUp),
+        hat(ABS_HAT0Y, 1, GamepadButton::DPadDown),
+    ]
+}
+
+/// Capabilities of a device in the order that the SDL indexes them.
+#[derive(Default)]
+#[cfg_attr(feature = "debug-derive", derive(Debug))]
+struct Layout {
+    keys: Vec<u16>,
+    axes: Vec<u16>,
+    hats: Vec<u16>,
+}
+
+impl Layout {
+    fn resolve(&self, mapping: &Mapping) -> Vec<(Input, Target)> {
+        let mut result = Vec::new();
+        for &(source, ref target) in &mapping.bindings {
+            let input = match source {
+                Source::Button(i) => match self.keys.get(i) {
+                    Some(&code) => Input::Key(code),
+                    None => continue,
+                },
+                Source::Axis {
+                    index,
+                    range,
+                    invert,
+                } => match self.axes.get(index) {
+                    Some(&code) => Input::Abs {
+                        code,
+                        range,
+                        invert,
+                    },
+                    None => continue,
+                },
+                Source::Hat { index, mask } => match self.hats.get(index) {
+                    Some(&code) => match mask {
+                        1 => Input::Hat {
+                            code: code + 1,
+                            direction: -1,
+                        },
+                        2 => Input::Hat { code, direction: 1 },
+                        4 => Input::Hat {
+                            code: code + 1,
+                            direction: 1,
+                        },
+                        8 => Input::Hat {
+                            code,
+                            direction: -1,
+                        },
+                        _ => continue,
+                    },
+                    None => continue,
+                },
+            };
+            result.push((input, target.clone()));
+        }
+        result
+    }
+}
+
+/// Linux's SDL GUID: bus, CRC, vendor, product and version as little-endian
+/// 16-bit values, each followed by a zero, except the CRC.
+fn make_guid(id: &libc::input_id) -> String {
+    let mut bytes = [0u8; 16];
+    for (i, v) in [id.bustype, 0, id.vendor, 0, id.product, 0, id.version, 0]
+        .iter()
+        .enumerate()
+    {
+        bytes[i * 2] = (*v & 0xff) as u8;
+        bytes[i * 2 + 1] = (*v >> 8) as u8;
+    }
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// CRC and version are ignored in the comparison, both GUIDs must be 32
+/// hexadecimal digits.
+fn guid_matches(a: &str, b: &str, with_version: bool) -> bool {
+    a[..4] == b[..4] && a[8..24] == b[8..24] && (!with_version || a[24..] == b[24..])
+}
+
+struct Device {
+    id: GamepadId,
+    file: File,
+    path: PathBuf,
+    guid: String,
+    name: String,
+    layout: Layout,
+    abs_info: BTreeMap<u16, libc::input_absinfo>,
+    bindings: Vec<(Input, Target)>,
+    pressed: BTreeSet<GamepadButton>,
+}
+
+impl Device {
+    /// Returns `None` when the device is not a joystick.
+    fn open(path: &Path) -> io::Result<Option<Self>> {
+        let file = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK | libc::O_CLOEXEC)
+            .open(path)?;
+        let ev_bits = get_bits(&file, 0, EV_MAX);
+        if !test_bit(&ev_bits, EV_KEY as usize) {
+            return Ok(None);
+        }
+        let key_bits = get_bits(&file, EV_KEY as usize, KEY_MAX);
+        if !(BTN_JOYSTICK..BTN_JOYSTICK + 0x20).any(|k| test_bit(&key_bits, k)) {
+            return Ok(None);
+        }
+        let abs_bits = if test_bit(&ev_bits, EV_ABS as usize) {
+            get_bits(&file, EV_ABS as usize, ABS_MAX)
+        } else {
+            vec![0u8; ABS_MAX / 8 + 1]
+        };
+        let mut layout = Layout::default();
+        for k in (BTN_JOYSTICK..KEY_MAX).chain(0..BTN_JOYSTICK) {
+            if test_bit(&key_bits, k) {
+                layout.keys.push(k as u16);
+            }
+        }
+        let mut abs_info = BTreeMap::new();
+        for a in 0..ABS_MAX {
+            if !test_bit(&abs_bits, a) {
+                continue;
+            }
+            let a = a as u16;
+            if !(ABS_HAT0X..=ABS_HAT3Y).contains(&a) {
+                layout.axes.push(a);
+            } else if (a - ABS_HAT0X).is_multiple_of(2) || !layout.hats.contains(&(a - 1)) {
+                layout.hats.push(a - (a - ABS_HAT0X) % 2);
+            }
+            let mut info: libc::input_absinfo = unsafe { zeroed() };
+            if ioctl(
+                &file,
+                ioc_read(0x40 + a as usize, size_of::<libc::input_absinfo>()),
+                &mut info,
+            ) {
+                abs_info.insert(a, info);
+            }
+        }
+        let mut name = [0u8; 256];
+        let name = if ioctl(&file, ioc_read(0x06, name.len()), name.as_mut_ptr()) {
+            let len = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+            String::from_utf8_lossy(&name[..len]).to_string()
+        } else {
+            path.to_string_lossy().to_string()
+        };
+        let mut input_id: libc::input_id = unsafe { zeroed() };
+        ioctl(
+            &file,
+            ioc_read(0x02, size_of::<libc::input_id>()),
+            &mut input_id,
+        );
+        Ok(Some(Self {
+            id: 0,
+            file,
+            path: path.to_path_buf(),
+            guid: make_guid(&input_id),
+            name,
+            layout,
+            abs_info,
+            bindings: Vec::new(),
+            pressed: BTreeSet::new(),
+        }))
+    }
+
+    fn bind(&mut self, mappings: &[Mapping]) {
+        let mapping = mappings
+            .iter()
+            .rev()
+            .find(|m| guid_matches(&m.guid, &self.guid, true))
+            .or_else(|| {
+                mappings
+                    .iter()
+                    .rev()
+                    .find(|m| guid_matches(&m.guid, &self.guid, false))
+            });
+        self.bindings = match mapping {
+            Some(m) => {
+                log_i!("Gamepad {} is mapped as {}", self.name, m.name);
+                self.layout.resolve(m)
+            }
+            None => default_bindings(),
+        };
+    }
+
+    /// Returns the normalized value of the absolute axis in [-1, 1].
+    fn normalize(&self, code: u16, value: i32) -> f64 {
+        match self.abs_info.get(&code) {
+            Some(i) if i.maximum > i.minimum => {
+                let v = (value - i.minimum) as f64 / (i.maximum - i.minimum) as f64;
+                (v * 2.0 - 1.0).clamp(-1.0, 1.0)
+            }
+            _ => value.signum() as f64,
+        }
+    }
+
+    fn handle(&mut self, engine: &Engine, type_: u16, code: u16, value: i32) {
+        let mut is_bound = false;
+        for i in 0..self.bindings.len() {
+            let (input, target) = self.bindings[i].clone();
+            if input.get_code() != (type_, code) {
+                continue;
+            }
+            is_bound = true;
+            let v = match input {
+                Input::Key(_) => {
+                    if value != 0 {
+                        1.0
+                    } else {
+                        0.0
+                    }
+                }
+                Input::Abs { range, invert, .. } => {
+                    let v = self.normalize(code, value);
+                    let v = if invert { -v } else { v };
+                    match range {
+                        Range::Full => v,
+                        Range::Positive => v.max(0.0),
+                        Range::Negative => (-v).max(0.0),
+                    }
+                }
+                Input::Hat { direction, .. } => {
+                    if value.signum() == direction {
+                        1.0
+                    } else {
+                        0.0
+                    }
+                }
+            };
+            match target {
+                Target::Button(b) => self.set_button(engine, b, v > 0.5),
+                Target::Axis(a, range) => {
+                    let v = match range {
+                        Range::Full => match (a, input) {
+                            (GamepadAxis::LeftTrigger, Input::Abs { .. })
+                            | (GamepadAxis::RightTrigger, Input::Abs { .. }) => (v + 1.0) * 0.5,
+                            _ => v,
+                        },
+                        Range::Positive => v,
+                        Range::Negative => -v,
+                    };
+                    engine.gamepad_axis_changed(self.id, a, v);
+                }
+            }
+        }
+        if is_bound {
+            return;
+        }
+        match type_ {
+            EV_KEY => self.set_button(engine, GamepadButton::Unknown(code as u32), value != 0),
+            EV_ABS => {
+                let v = self.normalize(code, value);
+                engine.gamepad_axis_changed(self.id, GamepadAxis::Unknown(code as u32), v);
+            }
+            _ => (),
+        }
+    }
+
+    fn set_button(&mut self, engine: &Engine, button: GamepadButton, pressed: bool) {
+        if pressed == self.pressed.contains(&button) {
+            return;
+        }
+        let b = Button::Gamepad {
+            id: self.id,
+            button: button.clone(),
+        };
+        if pressed {
+            self.pressed.insert(button);
+            engine.button_pressed(b);
+        } else {
+            self.pressed.remove(&button);
+            engine.button_released(b);
+        }
+    }
+
+    /// Returns false when the device is not available anymore.
+    fn read(&mut self, engine: &Engine) -> bool {
+        let mut events: [libc::input_event; 64] = unsafe { zeroed() };
+        loop {
+            let n = {
+                let buffer = unsafe {
+                    slice::from_raw_parts_mut(
+                        events.as_mut_ptr() as *mut u8,
+                        size_of::<[libc::input_event; 64]>(),
+                    )
+                };
+                match self.file.read(buffer) {
+                    Ok(0) => return false,
+                    Ok(n) => n / size_of::<libc::input_event>(),
+                    Err(ref e) if e.kind() == ErrorKind::WouldBlock => return true,
+                    Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                    Err(_) => return false,
+                }
+            };
+            for e in &events[..n] {
+                if e.type_ == EV_KEY || e.type_ == EV_ABS {
+                    self.handle(engine, e.type_, e.code, e.value);
+                }
+            }
+        }
+    }
+
+    /// Sends the current values of the axes.
+    fn sync_axes(&mut self, engine: &Engine) {
+        let values: Vec<(u16, i32)> = self
+            .abs_info
+            .iter()
+            .map(|(&code, info)| (code, info.value))
+            .collect();
+        for (code, value) in values {
+            self.handle(engine, EV_ABS, code, value);
+        }
+    }
+}
+
+/// Reads the gamepads from the evdev devices, new devices are found by
+/// periodically scanning the devices' directory.
+pub(crate) struct Gamepads {
+    devices: Vec<Device>,
+    ignored: BTreeSet<PathBuf>,
+    /// Devices that could not be opened, their errors are logged once.
+    failed: BTreeSet<PathBuf>,
+    mappings: Vec<Mapping>,
+    last_scan: Option<Instant>,
+}
+
+impl Gamepads {
+    /// Mappings of the `SDL_GAMECONTROLLERCONFIG` environment variable are
+    /// loaded too.
+    pub(crate) fn new() -> Self {
+        let mut result = Self {
+            devices: Vec::new(),
+            ignored: BTreeSet::new(),
+            failed: BTreeSet::new(),
+            mappings: Vec::new(),
+            last_scan: None,
+        };
+        if let Ok(db) = env::var("SDL_GAMECONTROLLERCONFIG") {
+            result.add_mappings(&db);
+        }
+        result
+    }
+
+    /// Adds the mappings of a game controller database, later mappings take
+    /// precedence and returns the number of the added mappings.
+    pub(crate) fn add_mappings(&mut self, db: &str) -> usize {
+        let count = self.mappings.len();
+        self.mappings.extend(db.lines().filter_map(Mapping::parse));
+        for d in &mut self.devices {
+            d.bind(&self.mappings);
+        }
+        self.mappings.len() - count
+    }
+
+    pub(crate) fn poll(&mut self, engine: &Engine) {
+        let now = Instant::now();
+        if self
+            .last_scan
+            .map(|t| now - t >= SCAN_INTERVAL)
+            .unwrap_or(true)
+        {
+            self.last_scan = Some(now);
+            self.scan(engine);
+        }
+        let mut i = 0;
+        while i < self.devices.len() {
+            if self.devices[i].read(engine) {
+                i += 1;
+                continue;
+            }
+            let d = self.devices.remove(i);
+            log_i!("Gamepad {} disconnected.", d.name);
+            engine.gamepad_disconnected(d.id);
+        }
+    }
+
+    fn scan(&mut self, engine: &Engine) {
+        let entries = match fs::read_dir(DEVICES_DIRECTORY) {
+            Ok(e) => e,
+            Err(e) => {
+                log_e!("Can not read {}: {}", DEVICES_DIRECTORY, e);
+                return;
+            }
+        };
+        let paths: BTreeSet<PathBuf> = entries
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_string_lossy().starts_with("event"))
+            .map(|e| e.path())
+            .collect();
+        self.ignored.retain(|p| paths.contains(p));
+        self.failed.retain(|p| paths.contains(p));
+        for path in paths {
+            if self.ignored.contains(&path) || self.devices.iter().any(|d| d.path == path) {
+                continue;
+            }
+            // Open errors are retried on the next scan, the device node may
+            // not have its permissions set yet right after the hot-plug.
+            let mut device = match Device::open(&path) {
+                Ok(Some(d)) => d,
+                Ok(None) => {
+                    self.failed.remove(&path);
+                    self.ignored.insert(path);
+                    continue;
+                }
+                Err(e) => {
+                    if !self.failed.contains(&path) {
+                        log_e!("Can not open {}: {}", path.display(), e);
+                        self.failed.insert(path);
+                    }
+                    continue;
+                }
+            };
+            self.failed.remove(&path);
+            device.id = (0..)
+                .find(|id| !self.devices.iter().any(|d| d.id == *id))
+                .unwrap_or(0);
+            device.bind(&self.mappings);
+            log_i!("Gamepad {} connected as {}.", device.name, device.id);
+            engine.gamepad_connected(device.id, device.name.clone());
+            device.sync_axes(engine);
+            self.devices.push(device);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::event::{DispatchMode, InputState},
+        log::{result_f, unwrap_f},
+        std::io::Write,
+    };
+
+    const XBOX_360: &str = "030000005e0400008e02000014010000,Xbox 360 Controller,a:b0,b:b1,\
+        back:b6,dpdown:h0.4,dpleft:h0.8,dpright:h0.2,dpup:h0.1,guide:b8,leftshoulder:b4,\
+        leftstick:b9,lefttrigger:a2,leftx:a0,lefty:a1~,rightshoulder:b5,rightstick:b10,\
+        righttrigger:a5,rightx:a3,righty:a4,start:b7,x:b2,y:b3,platform:Linux,";
+
+    #[test]
+    fn mappings_are_resolved_by_the_device_layout() {
+        let m = unwrap_mapping(Mapping::parse(XBOX_360));
+        assert_eq!(m.name, "Xbox 360 Controller");
+        let layout = Layout {
+            keys: vec![BTN_SOUTH, BTN_EAST, BTN_NORTH, BTN_WEST],
+            axes: vec![ABS_X, ABS_Y],
+            hats: vec![ABS_HAT0X],
+        };
+        let bindings = layout.resolve(&m);
+        assert!(bindings.contains(&(Input::Key(BTN_NORTH), Target::Button(GamepadButton::West))));
+        assert!(bindings.contains(&(
+            Input::Abs {
+                code: ABS_Y,
+                range: Range::Full,
+                invert: true,
+            },
+            Target::Axis(GamepadAxis::LeftY, Range::Full),
+        )));
+        assert!(bindings.contains(&(
+            Input::Hat {
+                code: ABS_HAT0Y,
+                direction: -1,
+            },
+            Target::Button(GamepadButton::DPadUp),
+        )));
+        assert!(!bindings
+            .iter()
+            .any(|b| b.1 == Target::Axis(GamepadAxis::RightX, Range::Full)));
+    }
+
+    #[test]
+    fn guids_match_without_crc_and_version() {
+        let guid = make_guid(&libc::input_id {
+            bustype: 3,
+            vendor: 0x045e,
+            product: 0x028e,
+            version: 0x0110,
+        });
+        assert_eq!(guid, "030000005e0400008e02000010010000");
+        assert!(guid_matches(
+            "0300abcd5e0400008e02000010010000",
+            &guid,
+            true
+        ));
+        assert!(!guid_matches(
+            "030000005e0400008e02000014010000",
+            &guid,
+            true
+        ));
+        assert!(guid_matches(
+            "030000005e0400008e02000014010000",
+            &guid,
+            false
+        ));
+        assert!(Mapping::parse(&XBOX_360.replace("Linux", "Windows")).is_none());
+        assert!(Mapping::parse(&XBOX_360.replacen("03", "é", 1)).is_none());
+        assert!(Mapping::parse(&XBOX_360.replacen("03", "0z", 1)).is_none());
+    }
+
+    const UINPUT_PAD: &str = "Virtual Test Pad";
+
+    /// A gamepad created through the uinput, it is destroyed on drop.
+    struct VirtualPad {
+        file: File,
+    }
+
+    impl VirtualPad {
+        fn create() -> io::Result<Self> {
+            let ioc = |dir: usize, nr: usize, size: usize| {
+                ((dir << 30) | (size << 16) | ((b'U' as usize) << 8) | nr) as libc::Ioctl
+            };
+            let set_bit = |file: &File, nr: usize, bit: u16| {
+                let r = unsafe { libc::ioctl(file.as_raw_fd(), ioc(1, nr, 4), bit as libc::c_int) };
+                if r < 0 {
+                    Err(io::Error::last_os_error())
+                } else {
+                    Ok(())
+                }
+            };
+            let file = OpenOptions::new()
+                .write(true)
+                .custom_flags(libc::O_NONBLOCK | libc::O_CLOEXEC)
+                .open("/dev/uinput")?;
+            set_bit(&file, 100, EV_KEY)?;
+            set_bit(&file, 100, EV_ABS)?;
+            set_bit(&file, 101, BTN_SOUTH)?;
+            set_bit(&file, 101, BTN_EAST)?;
+            set_bit(&file, 103, ABS_X)?;
+            let mut dev: libc::uinput_user_dev = unsafe { zeroed() };
+            for (d, s) in dev.name.iter_mut().zip(UINPUT_PAD.bytes()) {
+                *d = s as libc::c_char;
+            }
+            dev.id.bustype = 3;
+            dev.id.vendor = 0x1234;
+            dev.id.product = 0x5678;
+            dev.absmin[ABS_X as usize] = -100;
+            dev.absmax[ABS_X as usize] = 100;
+            let bytes = unsafe {
+                slice::from_raw_parts(
+                    &dev as *const libc::uinput_user_dev as *const u8,
+                    size_of::<libc::uinput_user_dev>(),
+                )
+            };
+            (&file).write_all(bytes)?;
+            if unsafe { libc::ioctl(file.as_raw_fd(), ioc(0, 1, 0)) } < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Self { file })
+        }
+
+        fn emit(&self, type_: u16, code: u16, value: i32) {
+            for &(type_, code, value) in &[(type_, code, value), (0, 0, 0)] {
+                let mut e: libc::input_event = unsafe { zeroed() };
+                e.type_ = type_;
+                e.code = code;
+                e.value = value;
+                let bytes = unsafe {
+                    slice::from_raw_parts(
+                        &e as *const libc::input_event as *const u8,
+                        size_of::<libc::input_event>(),
+                    )
+                };
+                result_f!((&self.file).write_all(bytes));
+            }
+        }
+    }
+
+    impl Drop for VirtualPad {
+        fn drop(&mut self) {
+            unsafe {
+                libc::ioctl(self.file.as_raw_fd(), (b'U' as libc::Ioctl) << 8 | 2);
+            }
+        }
+    }
+
+    /// Polls until the condition holds on the input state or a few seconds
+    /// passed.
+    fn poll_until<F: Fn(&InputState) -> bool>(
+        gamepads: &mut Gamepads,
+        engine: &Engine,
+        f: F,
+    ) -> bool {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            gamepads.scan(engine);
+            gamepads.poll(engine);
+            engine.dispatch();
+            if f(&engine.input_state()) {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    /// Needs the write access to `/dev/uinput` and the read access to the
+    /// created `/dev/input/event*` node.
+    #[test]
+    #[ignore]
+    fn uinput_pad_is_connected_pressed_moved_and_disconnected() {
+        let engine = Engine::new();
+        engine.set_dispatch_mode(DispatchMode::CallerThread);
+        let mut gamepads = Gamepads::new();
+        let pad = result_f!(VirtualPad::create());
+        let find = |s: &InputState| {
+            s.get_gamepads()
+                .iter()
+                .find(|g| g.1.get_name() == UINPUT_PAD)
+                .map(|g| *g.0)
+        };
+        assert!(poll_until(&mut gamepads, &engine, |s| find(s).is_some()));
+        let id = unwrap_f!(find(&engine.input_state()));
+        let south = Button::Gamepad {
+            id,
+            button: GamepadButton::South,
+        };
+        pad.emit(EV_KEY, BTN_SOUTH, 1);
+        assert!(poll_until(&mut gamepads, &engine, |s| s.is_pressed(&south)));
+        pad.emit(EV_ABS, ABS_X, 100);
+        assert!(poll_until(&mut gamepads, &engine, |s| {
+            s.get_gamepads()[&id].get_axis(GamepadAxis::LeftX) > 0.99
+        }));
+        pad.emit(EV_KEY, BTN_SOUTH, 0);
+        assert!(poll_until(&mut gamepads, &engine, |s| !s.is_pressed(&south)));
+        drop(pad);
+        assert!(poll_until(&mut gamepads, &engine, |s| find(s).is_none()));
+    }
+
+    fn unwrap_mapping(m: Option<Mapping>) -> Mapping {
+        match m {
+            Some(m) => m,
+            None => panic!("Mapping is not parsed"),
+        }
+    }
+}
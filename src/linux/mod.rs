@@ -1,6 +1,8 @@
+pub(crate) mod gamepad;
 pub(crate) mod glx;
 pub mod window;
 pub(crate) mod x11;
 pub(crate) mod x11_xcb;
 pub(crate) mod xcb;
+pub(crate) mod xinput;
 pub(crate) mod xproto;
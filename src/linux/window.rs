@@ -1,16 +1,25 @@
 use {
-    super::{glx, x11, x11_xcb, xcb, xproto},
+    super::{gamepad::Gamepads, glx, x11, x11_xcb, xcb, xinput, xproto},
     crate::event::*,
-    log::{log_e, log_f, log_i},
+    log::{log_e, log_f, log_i, result_f},
     std::{
+        cmp::{max, min},
         ffi::CString,
-        mem::{transmute, transmute_copy},
+        fs, io,
+        mem::{size_of, transmute, transmute_copy},
         os::raw::{c_int, c_uint},
+        path::Path,
         ptr::{null, null_mut},
-        sync::Arc,
+        sync::{Arc, Mutex},
     },
 };
 
+struct SizeConstraints {
+    min_size: Option<(i64, i64)>,
+    max_size: Option<(i64, i64)>,
+    resizable: bool,
+}
+
 pub struct Window {
     x11_lib: x11::X11,
     display: *mut x11::Display,
@@ -21,10 +30,14 @@ pub struct Window {
     #[cfg(feature = "gl")]
     glx_context: glx::Context,
     xcb_lib: xcb::Xcb,
+    xinput_lib: Option<xinput::XInput>,
+    pens: Mutex<Vec<xinput::Pen>>,
     connection: *mut xcb::Connection,
     screen: &'static mut xcb::Screen,
     window: xcb::Window,
     atom_wm_delete_window: *mut xcb::InternAtomReply,
+    size_constraints: Mutex<SizeConstraints>,
+    gamepads: Mutex<Gamepads>,
     event_engine: Engine,
 }
 
@@ -237,10 +250,31 @@ impl Window {
             unsafe { transmute(cs.as_ptr()) },
         );
         unsafe { libc::free(transmute(reply)) };
+        /* Root property changes are needed for tracking Xft.dpi changes */
+        let root_value_list = [xcb::EventMask::PROPERTY_CHANGE.bits()];
+        (xcb_lib.change_window_attributes)(
+            connection,
+            screen.root,
+            xcb::CW::EVENT_MASK.bits(),
+            root_value_list.as_ptr(),
+        );
+        let xinput_lib = xinput::XInput::new(&xcb_lib, connection);
+        let pens = if let Some(xinput_lib) = xinput_lib.as_ref() {
+            xinput_lib.select_touch_events(connection, window);
+            let pens = xinput_lib.query_pens(&xcb_lib, connection);
+            xinput_lib.select_pen_events(connection, window, &pens);
+            pens
+        } else {
+            Vec::new()
+        };
         (xcb_lib.map_window)(connection, window);
         (xcb_lib.flush)(connection);
         let event_engine = Engine::new();
-        event_engine.init_window_aspects(window_width as i64, window_height as i64);
+        event_engine.init_window_aspects(
+            window_width as i64,
+            window_height as i64,
+            Self::read_scale_factor(&xcb_lib, connection, screen),
+        );
         let result = Self {
             x11_lib,
             display,
@@ -249,32 +283,63 @@ impl Window {
             glx_window,
             glx_context,
             xcb_lib,
+            xinput_lib,
+            pens: Mutex::new(pens),
             connection,
             screen,
             window,
             atom_wm_delete_window,
+            size_constraints: Mutex::new(SizeConstraints {
+                min_size: None,
+                max_size: None,
+                resizable: true,
+            }),
+            gamepads: Mutex::new(Gamepads::new()),
             event_engine,
         };
         result
             .event_engine
             .init_mouse_position(result.get_mouse_position());
+        result
+            .event_engine
+            .init_window_position(result.get_window_position());
         Arc::new(result)
     }
 
     pub fn fetch_events(&self) {
+        self.event_engine.start_frame();
+        let mut is_moved = false;
         loop {
             let xcb_event = (self.xcb_lib.poll_for_event)(self.connection);
             if xcb_event == null_mut() {
                 break;
             }
-            self.translate(unsafe { transmute(xcb_event) });
+            self.translate(unsafe { &*xcb_event }, &mut is_moved);
             unsafe {
-                libc::free(transmute(xcb_event));
+                libc::free(xcb_event as *mut _);
             }
         }
+        // The position is queried once per batch, a drag of the window sends
+        // many configure notifications.
+        if is_moved {
+            self.event_engine.window_moved(self.get_window_position());
+        }
+        result_f!(self.gamepads.lock()).poll(&self.event_engine);
+        self.event_engine.dispatch();
+    }
+
+    /// Adds the gamepad mappings in the SDL's game controller database
+    /// format, returns the number of the added mappings.
+    pub fn add_gamepad_mappings(&self, db: &str) -> usize {
+        result_f!(self.gamepads.lock()).add_mappings(db)
+    }
+
+    pub fn load_gamepad_mappings<P: AsRef<Path>>(&self, path: P) -> io::Result<usize> {
+        Ok(self.add_gamepad_mappings(&fs::read_to_string(path)?))
     }
 
-    fn translate(&self, e: &xcb::GenericEvent) {
+    /// `is_moved` is set when the window's position must be queried.
+    fn translate(&self, e: &xcb::GenericEvent, is_moved: &mut bool) {
         let client_msg: &xcb::ClientMessageEvent = unsafe { transmute(e) };
         match e.response_type as c_uint & 0x7F {
             xproto::DESTROY_NOTIFY => {
@@ -293,13 +358,19 @@ impl Window {
             }
             xproto::BUTTON_PRESS => {
                 let press: &xcb::ButtonPressEvent = unsafe { transmute(e) };
-                self.event_engine
-                    .button_pressed(Self::translate_mouse_button(press.detail));
+                match Self::translate_wheel(press.detail) {
+                    Some(delta) => self.event_engine.wheel_scrolled(delta),
+                    None => self
+                        .event_engine
+                        .button_pressed(Self::translate_mouse_button(press.detail)),
+                }
             }
             xproto::BUTTON_RELEASE => {
                 let release: &xcb::ButtonReleaseEvent = unsafe { transmute(e) };
-                self.event_engine
-                    .button_released(Self::translate_mouse_button(release.detail));
+                if Self::translate_wheel(release.detail).is_none() {
+                    self.event_engine
+                        .button_released(Self::translate_mouse_button(release.detail));
+                }
             }
             xproto::KEY_PRESS => {
                 let press: &xcb::KeyPressEvent = unsafe { transmute(e) };
@@ -315,18 +386,102 @@ impl Window {
                 let e: &xcb::ConfigureNotifyEvent = unsafe { transmute(e) };
                 self.event_engine
                     .window_size_changed(e.width as i64, e.height as i64);
+                // Coordinates of a non-synthetic event are relative to the
+                // parent, which is the frame of the window manager.
+                if e.response_type & 0x80 != 0 {
+                    self.event_engine.window_moved((e.x as i64, e.y as i64));
+                } else {
+                    *is_moved = true;
+                }
             }
             xproto::RESIZE_REQUEST => {
                 let e: &xcb::ResizeRequestEvent = unsafe { transmute(e) };
-                self.event_engine
-                    .window_size_changed(e.width as i64, e.height as i64);
+                let (width, height) = match self.constrain_size((e.width as i64, e.height as i64)) {
+                    Some(s) => s,
+                    None => return,
+                };
+                self.configure(
+                    xcb::ConfigWindow::WIDTH | xcb::ConfigWindow::HEIGHT,
+                    &[width as u32, height as u32],
+                );
+                self.event_engine.window_size_changed(width, height);
+            }
+            xproto::EXPOSE => {
+                let e: &xcb::ExposeEvent = unsafe { transmute(e) };
+                self.event_engine.window_damaged(Rect::new(
+                    e.x as i64,
+                    e.y as i64,
+                    e.width as i64,
+                    e.height as i64,
+                ));
+            }
+            xproto::PROPERTY_NOTIFY => {
+                let e: &xcb::PropertyNotifyEvent = unsafe { transmute(e) };
+                if e.window == self.screen.root
+                    && e.atom == xcb::AtomEnum::ResourceManager as xcb::Atom
+                {
+                    self.event_engine
+                        .scale_factor_changed(Self::read_scale_factor(
+                            &self.xcb_lib,
+                            self.connection,
+                            self.screen,
+                        ));
+                }
             }
+            xproto::GE_GENERIC => self.translate_generic(e),
             c @ _ => {
                 log_i!("Uncontrolled event: {:?}", c);
             }
         }
     }
 
+    fn translate_generic(&self, e: &xcb::GenericEvent) {
+        let xinput_lib = match self.xinput_lib.as_ref() {
+            Some(l) => l,
+            None => return,
+        };
+        let e: &xinput::DeviceEvent = unsafe { transmute(e) };
+        if e.extension != xinput_lib.opcode {
+            return;
+        }
+        let x = xinput::fp1616_to_f64(e.event_x).round() as i64;
+        let y = xinput::fp1616_to_f64(e.event_y).round() as i64;
+        let index = e.detail as FingerIndexType;
+        match e.event_type {
+            xinput::TOUCH_BEGIN => self.event_engine.finger_down(x, y, index, 1.0, 0.0),
+            xinput::TOUCH_UPDATE => self.event_engine.finger_move(x, y, index, 1.0, 0.0),
+            xinput::TOUCH_END => self.event_engine.finger_up(x, y, index, 1.0, 0.0),
+            xinput::MOTION | xinput::BUTTON_PRESS | xinput::BUTTON_RELEASE => {
+                let mut pens = result_f!(self.pens.lock());
+                if let Some(pen) = pens.iter_mut().find(|p| p.id == e.deviceid) {
+                    pen.update(e);
+                    self.event_engine
+                        .pen_changed(pen.get_state((x, y), xinput::Pen::get_button(e)));
+                }
+            }
+            xinput::ENTER | xinput::LEAVE => {
+                let e = unsafe { &*(e as *const xinput::DeviceEvent as *const xinput::EnterEvent) };
+                let mut pens = result_f!(self.pens.lock());
+                if let Some(pen) = pens.iter_mut().find(|p| p.id == e.sourceid) {
+                    pen.set_proximity(e.event_type == xinput::ENTER);
+                    self.event_engine.pen_changed(pen.get_state((x, y), None));
+                }
+            }
+            t => log_i!("Uncontrolled XInput event: {}", t),
+        }
+    }
+
+    /// Each step of the wheel is a press and a release of the buttons 4 to 7.
+    fn translate_wheel(i: u8) -> Option<(f64, f64)> {
+        match i {
+            4 => Some((0.0, -1.0)),
+            5 => Some((0.0, 1.0)),
+            6 => Some((-1.0, 0.0)),
+            7 => Some((1.0, 0.0)),
+            _ => None,
+        }
+    }
+
     fn translate_mouse_button(i: u8) -> Button {
         let b: xcb::ButtonIndex = unsafe { transmute(i as u32) };
         Button::Mouse(match b {
@@ -535,10 +690,196 @@ impl Window {
         result
     }
 
+    /// Scale factor comes from Xft.dpi of the root window's resource manager
+    /// and if it does not exist, from the physical size of the screen.
+    fn read_scale_factor(
+        xcb_lib: &xcb::Xcb,
+        connection: *mut xcb::Connection,
+        screen: &xcb::Screen,
+    ) -> f64 {
+        const BASE_DPI: f64 = 96.0;
+        let cookie = (xcb_lib.get_property)(
+            connection,
+            0,
+            screen.root,
+            xcb::AtomEnum::ResourceManager as xcb::Atom,
+            xcb::AtomEnum::String as xcb::Atom,
+            0,
+            u32::MAX / 4,
+        );
+        let reply = (xcb_lib.get_property_reply)(connection, cookie, null_mut());
+        if !reply.is_null() {
+            let len = (xcb_lib.get_property_value_length)(reply);
+            let value = (xcb_lib.get_property_value)(reply);
+            let dpi = if len > 0 && !value.is_null() {
+                let bytes = unsafe { std::slice::from_raw_parts(value as *const u8, len as usize) };
+                String::from_utf8_lossy(bytes)
+                    .lines()
+                    .filter_map(|l| {
+                        let mut kv = l.splitn(2, ':');
+                        match (kv.next(), kv.next()) {
+                            (Some(k), Some(v)) if k.trim() == "Xft.dpi" => v.trim().parse().ok(),
+                            _ => None,
+                        }
+                    })
+                    .next()
+            } else {
+                None
+            };
+            unsafe {
+                libc::free(reply as *mut _);
+            }
+            if let Some(dpi) = dpi {
+                let dpi: f64 = dpi;
+                if dpi > 0.0 {
+                    return dpi / BASE_DPI;
+                }
+            }
+        }
+        if screen.width_in_millimeters == 0 {
+            return 1.0;
+        }
+        let dpi = screen.width_in_pixels as f64 * 25.4 / screen.width_in_millimeters as f64;
+        // Physical sizes reported by monitors are not precise, so this makes
+        // the factor to snap into quarter steps.
+        let factor = (dpi / BASE_DPI * 4.0).round() / 4.0;
+        if factor < 1.0 {
+            1.0
+        } else {
+            factor
+        }
+    }
+
+    fn get_window_position(&self) -> (i64, i64) {
+        let cookie = (self.xcb_lib.translate_coordinates)(
+            self.connection,
+            self.window,
+            self.screen.root,
+            0,
+            0,
+        );
+        let reply = (self.xcb_lib.translate_coordinates_reply)(self.connection, cookie, null_mut());
+        if reply.is_null() {
+            log_e!("Can not fetch window position.");
+            return self.event_engine.get_window_position();
+        }
+        let result = unsafe { ((*reply).dst_x as i64, (*reply).dst_y as i64) };
+        unsafe {
+            libc::free(reply as *mut _);
+        }
+        result
+    }
+
     pub fn get_event_engine(&self) -> &Engine {
         &self.event_engine
     }
 
+    pub fn request_redraw(&self) {
+        self.event_engine.request_redraw();
+    }
+
+    pub fn set_position(&self, x: i64, y: i64) {
+        self.configure(
+            xcb::ConfigWindow::X | xcb::ConfigWindow::Y,
+            &[x as i32 as u32, y as i32 as u32],
+        );
+    }
+
+    pub fn set_size(&self, width: i64, height: i64) {
+        if width <= 0 || height <= 0 {
+            log_e!("Invalid window size: {}x{}", width, height);
+            return;
+        }
+        // The size of a non-resizable window is only changed by the program.
+        let (width, height) = match self.constrain_size((width, height)) {
+            Some(size) => size,
+            None => {
+                self.update_size_hints((width, height));
+                (width, height)
+            }
+        };
+        self.configure(
+            xcb::ConfigWindow::WIDTH | xcb::ConfigWindow::HEIGHT,
+            &[width as u32, height as u32],
+        );
+    }
+
+    pub fn set_min_size(&self, size: Option<(i64, i64)>) {
+        result_f!(self.size_constraints.lock()).min_size = size;
+        self.update_size_hints(self.get_size());
+    }
+
+    pub fn set_max_size(&self, size: Option<(i64, i64)>) {
+        result_f!(self.size_constraints.lock()).max_size = size;
+        self.update_size_hints(self.get_size());
+    }
+
+    pub fn set_resizable(&self, resizable: bool) {
+        result_f!(self.size_constraints.lock()).resizable = resizable;
+        self.update_size_hints(self.get_size());
+    }
+
+    fn get_size(&self) -> (i64, i64) {
+        self.event_engine.get_window_aspects().get_physical_size()
+    }
+
+    /// Clamps the requested size into the minimum and maximum sizes, returns
+    /// `None` when the window is not resizable.
+    fn constrain_size(&self, size: (i64, i64)) -> Option<(i64, i64)> {
+        let constraints = result_f!(self.size_constraints.lock());
+        if !constraints.resizable {
+            return None;
+        }
+        let (mut width, mut height) = size;
+        if let Some((w, h)) = constraints.min_size {
+            width = max(width, w);
+            height = max(height, h);
+        }
+        if let Some((w, h)) = constraints.max_size {
+            width = min(width, w);
+            height = min(height, h);
+        }
+        Some((width, height))
+    }
+
+    fn configure(&self, mask: xcb::ConfigWindow, values: &[u32]) {
+        (self.xcb_lib.configure_window)(self.connection, self.window, mask.bits(), values.as_ptr());
+        (self.xcb_lib.flush)(self.connection);
+    }
+
+    /// A non-resizable window has its minimum and maximum sizes equal to the
+    /// `size`.
+    fn update_size_hints(&self, size: (i64, i64)) {
+        let constraints = result_f!(self.size_constraints.lock());
+        let (min_size, max_size) = if constraints.resizable {
+            (constraints.min_size, constraints.max_size)
+        } else {
+            (Some(size), Some(size))
+        };
+        let mut hints = xcb::SizeHints::default();
+        if let Some((w, h)) = min_size {
+            hints.flags |= xcb::SizeHint::P_MIN_SIZE.bits();
+            hints.min_width = w as i32;
+            hints.min_height = h as i32;
+        }
+        if let Some((w, h)) = max_size {
+            hints.flags |= xcb::SizeHint::P_MAX_SIZE.bits();
+            hints.max_width = w as i32;
+            hints.max_height = h as i32;
+        }
+        (self.xcb_lib.change_property)(
+            self.connection,
+            xproto::PropMode::Replace as u8,
+            self.window,
+            xcb::AtomEnum::WmNormalHints as u32,
+            xcb::AtomEnum::WmSizeHints as u32,
+            32,
+            (size_of::<xcb::SizeHints>() / size_of::<u32>()) as u32,
+            &hints as *const xcb::SizeHints as *const _,
+        );
+        (self.xcb_lib.flush)(self.connection);
+    }
+
     #[cfg(feature = "gl")]
     pub fn swap(&self) {
         (self.glx_lib.swap_buffers)(self.display, self.glx_window);
@@ -55,6 +55,33 @@ bitflags! {
     }
 }
 
+bitflags! {
+    pub(crate) struct ConfigWindow: u16 {
+        const X = 1;
+        const Y = 2;
+        const WIDTH = 4;
+        const HEIGHT = 8;
+        const BORDER_WIDTH = 16;
+        const SIBLING = 32;
+        const STACK_MODE = 64;
+    }
+}
+
+bitflags! {
+    pub(crate) struct SizeHint: u32 {
+        const US_POSITION = 1;
+        const US_SIZE = 2;
+        const P_POSITION = 4;
+        const P_SIZE = 8;
+        const P_MIN_SIZE = 16;
+        const P_MAX_SIZE = 32;
+        const P_RESIZE_INC = 64;
+        const P_ASPECT = 128;
+        const P_BASE_SIZE = 256;
+        const P_WIN_GRAVITY = 512;
+    }
+}
+
 #[repr(u32)]
 #[derive(Copy, Clone)]
 #[cfg_attr(debug_mode, derive(Debug))]
@@ -91,7 +118,7 @@ pub(crate) enum AtomEnum {
     _Pixmap = 20,
     _Point = 21,
     _Rectangle = 22,
-    _ResourceManager = 23,
+    ResourceManager = 23,
     _RgbColorMap = 24,
     _RgbBestMap = 25,
     _RgbBlueMap = 26,
@@ -108,8 +135,8 @@ pub(crate) enum AtomEnum {
     _WmIconName = 37,
     _WmIconSize = 38,
     WmName = 39,
-    _WmNormalHints = 40,
-    _WmSizeHints = 41,
+    WmNormalHints = 40,
+    WmSizeHints = 41,
     _WmZoomHints = 42,
     _MinSpace = 43,
     _NormSpace = 44,
@@ -153,6 +180,10 @@ pub(crate) enum ButtonIndex {
 #[cfg_attr(debug_mode, derive(Debug))]
 pub enum Connection {}
 
+#[derive(Copy, Clone)]
+#[cfg_attr(debug_mode, derive(Debug))]
+pub(crate) enum Extension {}
+
 pub type Window = u32;
 pub(crate) type ColorMap = u32;
 pub(crate) type VisualId = u32;
@@ -211,6 +242,37 @@ impl Default for InternAtomReply {
     }
 }
 
+#[repr(C)]
+#[derive(Copy, Clone)]
+#[cfg_attr(debug_mode, derive(Debug))]
+pub(crate) struct GenericReply {
+    pub(crate) response_type: u8,
+    pub(crate) pad0: u8,
+    pub(crate) sequence: u16,
+    pub(crate) length: u32,
+    pub(crate) pad: [u32; 6usize],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+#[cfg_attr(debug_mode, derive(Debug))]
+pub(crate) struct QueryExtensionReply {
+    pub(crate) response_type: u8,
+    pub(crate) pad0: u8,
+    pub(crate) sequence: u16,
+    pub(crate) length: u32,
+    pub(crate) present: u8,
+    pub(crate) major_opcode: u8,
+    pub(crate) first_event: u8,
+    pub(crate) first_error: u8,
+}
+
+impl Default for QueryExtensionReply {
+    fn default() -> Self {
+        unsafe { zeroed() }
+    }
+}
+
 #[repr(C)]
 #[derive(Copy, Clone)]
 #[cfg_attr(debug_mode, derive(Debug))]
@@ -416,6 +478,128 @@ pub(crate) struct ResizeRequestEvent {
     pub(crate) height: u16,
 }
 
+#[repr(C)]
+pub(crate) struct ExposeEvent {
+    pub(crate) response_type: u8,
+    pub(crate) pad0: u8,
+    pub(crate) sequence: u16,
+    pub(crate) window: Window,
+    pub(crate) x: u16,
+    pub(crate) y: u16,
+    pub(crate) width: u16,
+    pub(crate) height: u16,
+    pub(crate) count: u16,
+    pub(crate) pad1: [u8; 2usize],
+}
+
+#[repr(C)]
+pub(crate) struct PropertyNotifyEvent {
+    pub(crate) response_type: u8,
+    pub(crate) pad0: u8,
+    pub(crate) sequence: u16,
+    pub(crate) window: Window,
+    pub(crate) atom: Atom,
+    pub(crate) time: TimeStamp,
+    pub(crate) state: u8,
+    pub(crate) pad1: [u8; 3usize],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+#[cfg_attr(debug_mode, derive(Debug))]
+pub(crate) struct GetPropertyCookie {
+    pub(crate) sequence: c_uint,
+}
+
+impl Default for GetPropertyCookie {
+    fn default() -> Self {
+        unsafe { zeroed() }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+#[cfg_attr(debug_mode, derive(Debug))]
+pub(crate) struct GetPropertyReply {
+    pub(crate) response_type: u8,
+    pub(crate) format: u8,
+    pub(crate) sequence: u16,
+    pub(crate) length: u32,
+    pub(crate) type_: Atom,
+    pub(crate) bytes_after: u32,
+    pub(crate) value_len: u32,
+    pub(crate) pad0: [u8; 12usize],
+}
+
+impl Default for GetPropertyReply {
+    fn default() -> Self {
+        unsafe { zeroed() }
+    }
+}
+
+/// Layout of the WM_SIZE_HINTS property (WM_NORMAL_HINTS).
+#[repr(C)]
+#[derive(Copy, Clone)]
+#[cfg_attr(debug_mode, derive(Debug))]
+pub(crate) struct SizeHints {
+    pub(crate) flags: u32,
+    pub(crate) x: i32,
+    pub(crate) y: i32,
+    pub(crate) width: i32,
+    pub(crate) height: i32,
+    pub(crate) min_width: i32,
+    pub(crate) min_height: i32,
+    pub(crate) max_width: i32,
+    pub(crate) max_height: i32,
+    pub(crate) width_inc: i32,
+    pub(crate) height_inc: i32,
+    pub(crate) min_aspect_num: i32,
+    pub(crate) min_aspect_den: i32,
+    pub(crate) max_aspect_num: i32,
+    pub(crate) max_aspect_den: i32,
+    pub(crate) base_width: i32,
+    pub(crate) base_height: i32,
+    pub(crate) win_gravity: u32,
+}
+
+impl Default for SizeHints {
+    fn default() -> Self {
+        unsafe { zeroed() }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+#[cfg_attr(debug_mode, derive(Debug))]
+pub(crate) struct TranslateCoordinatesCookie {
+    pub(crate) sequence: c_uint,
+}
+
+impl Default for TranslateCoordinatesCookie {
+    fn default() -> Self {
+        unsafe { zeroed() }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+#[cfg_attr(debug_mode, derive(Debug))]
+pub(crate) struct TranslateCoordinatesReply {
+    pub(crate) response_type: u8,
+    pub(crate) same_screen: u8,
+    pub(crate) sequence: u16,
+    pub(crate) length: u32,
+    pub(crate) child: Window,
+    pub(crate) dst_x: i16,
+    pub(crate) dst_y: i16,
+}
+
+impl Default for TranslateCoordinatesReply {
+    fn default() -> Self {
+        unsafe { zeroed() }
+    }
+}
+
 #[repr(C)]
 #[derive(Copy, Clone)]
 #[cfg_attr(debug_mode, derive(Debug))]
@@ -497,6 +681,46 @@ pub(crate) struct Xcb {
         data_len: u32,
         data: *const c_void,
     ) -> VoidCookie,
+    pub(crate) change_window_attributes: extern "C" fn(
+        c: *mut Connection,
+        window: Window,
+        value_mask: u32,
+        value_list: *const u32,
+    ) -> VoidCookie,
+    pub(crate) get_property: extern "C" fn(
+        c: *mut Connection,
+        delete: u8,
+        window: Window,
+        property: Atom,
+        type_: Atom,
+        long_offset: u32,
+        long_length: u32,
+    ) -> GetPropertyCookie,
+    pub(crate) get_property_reply: extern "C" fn(
+        c: *mut Connection,
+        cookie: GetPropertyCookie,
+        e: *mut *mut GenericError,
+    ) -> *mut GetPropertyReply,
+    pub(crate) get_property_value: extern "C" fn(r: *const GetPropertyReply) -> *mut c_void,
+    pub(crate) get_property_value_length: extern "C" fn(r: *const GetPropertyReply) -> c_int,
+    pub(crate) configure_window: extern "C" fn(
+        c: *mut Connection,
+        window: Window,
+        value_mask: u16,
+        value_list: *const u32,
+    ) -> VoidCookie,
+    pub(crate) translate_coordinates: extern "C" fn(
+        c: *mut Connection,
+        src_window: Window,
+        dst_window: Window,
+        src_x: i16,
+        src_y: i16,
+    ) -> TranslateCoordinatesCookie,
+    pub(crate) translate_coordinates_reply: extern "C" fn(
+        c: *mut Connection,
+        cookie: TranslateCoordinatesCookie,
+        e: *mut *mut GenericError,
+    ) -> *mut TranslateCoordinatesReply,
     pub(crate) map_window: extern "C" fn(c: *mut Connection, window: Window) -> VoidCookie,
     pub(crate) flush: extern "C" fn(c: *mut Connection) -> c_int,
     pub(crate) poll_for_event: extern "C" fn(c: *mut Connection) -> *mut GenericEvent,
@@ -511,6 +735,9 @@ pub(crate) struct Xcb {
     // pub(crate) disconnect: extern "C" fn(*mut Connection),
     pub(crate) create_colormap:
         extern "C" fn(*mut Connection, u8, ColorMap, Window, VisualId) -> VoidCookie,
+    /// The returned reply is owned by the connection.
+    pub(crate) get_extension_data:
+        extern "C" fn(*mut Connection, *mut Extension) -> *const QueryExtensionReply,
     _lib: Linker,
 }
 
@@ -532,6 +759,14 @@ impl Xcb {
             intern_atom: fun!(intern_atom),
             intern_atom_reply: fun!(intern_atom_reply),
             change_property: fun!(change_property),
+            change_window_attributes: fun!(change_window_attributes),
+            get_property: fun!(get_property),
+            get_property_reply: fun!(get_property_reply),
+            get_property_value: fun!(get_property_value),
+            get_property_value_length: fun!(get_property_value_length),
+            configure_window: fun!(configure_window),
+            translate_coordinates: fun!(translate_coordinates),
+            translate_coordinates_reply: fun!(translate_coordinates_reply),
             map_window: fun!(map_window),
             flush: fun!(flush),
             poll_for_event: fun!(poll_for_event),
@@ -540,6 +775,7 @@ impl Xcb {
             destroy_window: fun!(destroy_window),
             // disconnect: fun!(disconnect),
             create_colormap: fun!(create_colormap),
+            get_extension_data: fun!(get_extension_data),
             _lib,
         }
     }
@@ -0,0 +1,566 @@
+use {
+    super::xcb,
+    crate::event::{ButtonAction, PenButton, PenState},
+    library_loader::Linker,
+    log::{log_e, unwrap_f},
+    std::{ffi::CString, mem::size_of, os::raw::c_uint, ptr::null_mut, slice},
+};
+
+pub(crate) type DeviceId = u16;
+pub(crate) type Fp1616 = i32;
+
+pub(crate) const ALL_DEVICES: DeviceId = 0;
+pub(crate) const ALL_MASTER_DEVICES: DeviceId = 1;
+
+pub(crate) const BUTTON_PRESS: u16 = 4;
+pub(crate) const BUTTON_RELEASE: u16 = 5;
+pub(crate) const MOTION: u16 = 6;
+pub(crate) const ENTER: u16 = 7;
+pub(crate) const LEAVE: u16 = 8;
+pub(crate) const TOUCH_BEGIN: u16 = 18;
+pub(crate) const TOUCH_UPDATE: u16 = 19;
+pub(crate) const TOUCH_END: u16 = 20;
+
+const SLAVE_POINTER: u16 = 3;
+const VALUATOR_CLASS: u16 = 2;
+
+/// Touch events need the version 2.2 of the extension.
+const MAJOR_VERSION: u16 = 2;
+const MINOR_VERSION: u16 = 2;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+#[cfg_attr(debug_mode, derive(Debug))]
+pub(crate) struct XiQueryVersionCookie {
+    pub(crate) sequence: c_uint,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+#[cfg_attr(debug_mode, derive(Debug))]
+pub(crate) struct XiQueryDeviceCookie {
+    pub(crate) sequence: c_uint,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+#[cfg_attr(debug_mode, derive(Debug))]
+pub(crate) struct XiQueryVersionReply {
+    pub(crate) response_type: u8,
+    pub(crate) pad0: u8,
+    pub(crate) sequence: u16,
+    pub(crate) length: u32,
+    pub(crate) major_version: u16,
+    pub(crate) minor_version: u16,
+    pub(crate) pad1: [u8; 20usize],
+}
+
+/// An event mask of a device with a single word of mask.
+#[repr(C)]
+#[derive(Copy, Clone)]
+#[cfg_attr(debug_mode, derive(Debug))]
+pub(crate) struct EventMask {
+    pub(crate) deviceid: DeviceId,
+    pub(crate) mask_len: u16,
+    pub(crate) mask: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+#[cfg_attr(debug_mode, derive(Debug))]
+pub(crate) struct ModifierInfo {
+    pub(crate) base: u32,
+    pub(crate) latched: u32,
+    pub(crate) locked: u32,
+    pub(crate) effective: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+#[cfg_attr(debug_mode, derive(Debug))]
+pub(crate) struct GroupInfo {
+    pub(crate) base: u8,
+    pub(crate) latched: u8,
+    pub(crate) locked: u8,
+    pub(crate) effective: u8,
+}
+
+/// Layout of the device events like the button, motion and touch events,
+/// `detail` is the button or the touch id and the positions are 16.16 fixed
+/// point numbers. It is followed by the masks of the buttons and the
+/// valuators, and then by the values of the valuators in the mask.
+#[repr(C)]
+#[derive(Copy, Clone)]
+#[cfg_attr(debug_mode, derive(Debug))]
+pub(crate) struct DeviceEvent {
+    pub(crate) response_type: u8,
+    pub(crate) extension: u8,
+    pub(crate) sequence: u16,
+    pub(crate) length: u32,
+    pub(crate) event_type: u16,
+    pub(crate) deviceid: DeviceId,
+    pub(crate) time: xcb::TimeStamp,
+    pub(crate) detail: u32,
+    pub(crate) root: xcb::Window,
+    pub(crate) event: xcb::Window,
+    pub(crate) child: xcb::Window,
+    pub(crate) full_sequence: u32,
+    pub(crate) root_x: Fp1616,
+    pub(crate) root_y: Fp1616,
+    pub(crate) event_x: Fp1616,
+    pub(crate) event_y: Fp1616,
+    pub(crate) buttons_len: u16,
+    pub(crate) valuators_len: u16,
+    pub(crate) sourceid: DeviceId,
+    pub(crate) pad0: [u8; 2usize],
+    pub(crate) flags: u32,
+    pub(crate) mods: ModifierInfo,
+    pub(crate) group: GroupInfo,
+}
+
+/// Layout of the enter and leave events of the master pointer, `sourceid` is
+/// the slave device that has moved it.
+#[repr(C)]
+#[derive(Copy, Clone)]
+#[cfg_attr(debug_mode, derive(Debug))]
+pub(crate) struct EnterEvent {
+    pub(crate) response_type: u8,
+    pub(crate) extension: u8,
+    pub(crate) sequence: u16,
+    pub(crate) length: u32,
+    pub(crate) event_type: u16,
+    pub(crate) deviceid: DeviceId,
+    pub(crate) time: xcb::TimeStamp,
+    pub(crate) sourceid: DeviceId,
+    pub(crate) mode: u8,
+    pub(crate) detail: u8,
+    pub(crate) root: xcb::Window,
+    pub(crate) event: xcb::Window,
+    pub(crate) child: xcb::Window,
+    pub(crate) full_sequence: u32,
+    pub(crate) root_x: Fp1616,
+    pub(crate) root_y: Fp1616,
+    pub(crate) event_x: Fp1616,
+    pub(crate) event_y: Fp1616,
+    pub(crate) same_screen: u8,
+    pub(crate) focus: u8,
+    pub(crate) buttons_len: u16,
+    pub(crate) mods: ModifierInfo,
+    pub(crate) group: GroupInfo,
+}
+
+impl DeviceEvent {
+    /// Returns the numbers and the values of the valuators in the event.
+    pub(crate) fn get_valuators(&self) -> Vec<(u16, f64)> {
+        let mut result = Vec::new();
+        unsafe {
+            let base = (self as *const Self).add(1) as *const u32;
+            let mask = slice::from_raw_parts(
+                base.add(self.buttons_len as usize),
+                self.valuators_len as usize,
+            );
+            let mut value = base.add(self.buttons_len as usize + self.valuators_len as usize);
+            for (w, bits) in mask.iter().enumerate() {
+                for b in 0..32 {
+                    if bits & (1 << b) != 0 {
+                        result.push((
+                            (w * 32 + b) as u16,
+                            fp3232_to_f64(*(value as *const i32), *value.add(1)),
+                        ));
+                        value = value.add(2);
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+pub(crate) fn fp1616_to_f64(v: Fp1616) -> f64 {
+    v as f64 / 65536.0
+}
+
+fn fp3232_to_f64(integral: i32, frac: u32) -> f64 {
+    integral as f64 + frac as f64 / 4294967296.0
+}
+
+fn read_u16(b: &[u8], offset: usize) -> Option<u16> {
+    b.get(offset..offset + 2)
+        .map(|b| u16::from_ne_bytes([b[0], b[1]]))
+}
+
+fn read_u32(b: &[u8], offset: usize) -> Option<u32> {
+    b.get(offset..offset + 4)
+        .map(|b| u32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn read_fp3232(b: &[u8], offset: usize) -> Option<f64> {
+    Some(fp3232_to_f64(
+        read_u32(b, offset)? as i32,
+        read_u32(b, offset + 4)?,
+    ))
+}
+
+#[derive(Clone, Copy)]
+#[cfg_attr(debug_mode, derive(Debug))]
+pub(crate) struct Valuator {
+    number: u16,
+    min: f64,
+    max: f64,
+    /// Units per meter, or per radian for the angles, zero when unknown.
+    resolution: u32,
+}
+
+impl Valuator {
+    /// Maps the value into the range of 0 to 1.
+    fn normalize(&self, v: f64) -> f64 {
+        if self.max > self.min {
+            ((v - self.min) / (self.max - self.min)).clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+
+    fn degrees(self, v: f64) -> f64 {
+        if self.resolution > 0 {
+            (v / self.resolution as f64).to_degrees()
+        } else {
+            self.normalize(v) * 180.0 - 90.0
+        }
+    }
+}
+
+/// Atoms of the valuator labels.
+struct Labels {
+    pressure: xcb::Atom,
+    tilt_x: xcb::Atom,
+    tilt_y: xcb::Atom,
+    wheel: xcb::Atom,
+    rotation: xcb::Atom,
+}
+
+impl Labels {
+    fn new(xcb_lib: &xcb::Xcb, connection: *mut xcb::Connection) -> Self {
+        let intern = |name: &str| {
+            let cs = CString::new(name).unwrap();
+            let cookie = (xcb_lib.intern_atom)(connection, 1, name.len() as u16, cs.as_ptr());
+            let reply = (xcb_lib.intern_atom_reply)(connection, cookie, null_mut());
+            if reply.is_null() {
+                return 0;
+            }
+            let atom = unsafe { (*reply).atom };
+            unsafe {
+                libc::free(reply as *mut _);
+            }
+            atom
+        };
+        Self {
+            pressure: intern("Abs Pressure"),
+            tilt_x: intern("Abs Tilt X"),
+            tilt_y: intern("Abs Tilt Y"),
+            wheel: intern("Abs Wheel"),
+            rotation: intern("Abs Rotation"),
+        }
+    }
+}
+
+/// A tablet tool, the devices with a pressure valuator are considered pens.
+#[cfg_attr(debug_mode, derive(Debug))]
+pub(crate) struct Pen {
+    pub(crate) id: DeviceId,
+    eraser: bool,
+    pressure: Valuator,
+    tilt_x: Option<Valuator>,
+    tilt_y: Option<Valuator>,
+    rotation: Option<Valuator>,
+    pressure_value: f64,
+    tilt_value: (f64, f64),
+    rotation_value: f64,
+    proximity: bool,
+}
+
+impl Pen {
+    /// Valuators only have the changed values, so the others are kept.
+    pub(crate) fn update(&mut self, e: &DeviceEvent) {
+        self.proximity = true;
+        for (number, v) in e.get_valuators() {
+            if number == self.pressure.number {
+                self.pressure_value = self.pressure.normalize(v);
+            }
+            match self.tilt_x {
+                Some(t) if t.number == number => self.tilt_value.0 = t.degrees(v),
+                _ => (),
+            }
+            match self.tilt_y {
+                Some(t) if t.number == number => self.tilt_value.1 = t.degrees(v),
+                _ => (),
+            }
+            match self.rotation {
+                Some(r) if r.number == number => self.rotation_value = r.normalize(v) * 360.0,
+                _ => (),
+            }
+        }
+    }
+
+    /// The pen is in the proximity while it is in the window, X11 does not
+    /// report the proximity of the tablet's tools.
+    pub(crate) fn set_proximity(&mut self, proximity: bool) {
+        self.proximity = proximity;
+    }
+
+    pub(crate) fn get_state(
+        &self,
+        position: (i64, i64),
+        button: Option<(PenButton, ButtonAction)>,
+    ) -> PenState {
+        PenState {
+            position,
+            pressure: self.pressure_value,
+            tilt: self.tilt_value,
+            rotation: self.rotation_value,
+            button,
+            eraser: self.eraser,
+            proximity: self.proximity,
+        }
+    }
+
+    pub(crate) fn get_button(e: &DeviceEvent) -> Option<(PenButton, ButtonAction)> {
+        let action = match e.event_type {
+            BUTTON_PRESS => ButtonAction::Press,
+            BUTTON_RELEASE => ButtonAction::Release,
+            _ => return None,
+        };
+        let button = match e.detail {
+            0 | 1 => PenButton::Tip,
+            b => PenButton::Barrel(b - 1),
+        };
+        Some((button, action))
+    }
+
+    /// Parses a device info of the query device reply, returns it with its
+    /// size.
+    fn parse(b: &[u8], labels: &Labels) -> Option<(Option<Self>, usize)> {
+        let id = read_u16(b, 0)?;
+        let type_ = read_u16(b, 2)?;
+        let num_classes = read_u16(b, 6)?;
+        let name_len = read_u16(b, 8)? as usize;
+        let name = String::from_utf8_lossy(b.get(12..12 + name_len)?);
+        let mut offset = 12 + name_len.div_ceil(4) * 4;
+        let mut valuators = Vec::new();
+        for _ in 0..num_classes {
+            let class_type = read_u16(b, offset)?;
+            let len = read_u16(b, offset + 2)? as usize * 4;
+            if len == 0 {
+                return None;
+            }
+            if class_type == VALUATOR_CLASS {
+                valuators.push((
+                    read_u32(b, offset + 8)?,
+                    Valuator {
+                        number: read_u16(b, offset + 6)?,
+                        min: read_fp3232(b, offset + 12)?,
+                        max: read_fp3232(b, offset + 20)?,
+                        resolution: read_u32(b, offset + 36)?,
+                    },
+                ));
+            }
+            offset += len;
+        }
+        let find = |label: xcb::Atom| {
+            valuators
+                .iter()
+                .find(|&&(l, _)| l != 0 && l == label)
+                .map(|&(_, v)| v)
+        };
+        let pressure = match find(labels.pressure) {
+            Some(p) if type_ == SLAVE_POINTER => p,
+            _ => return Some((None, offset)),
+        };
+        Some((
+            Some(Self {
+                id,
+                eraser: name.to_lowercase().contains("eraser"),
+                pressure,
+                tilt_x: find(labels.tilt_x),
+                tilt_y: find(labels.tilt_y),
+                rotation: find(labels.rotation).or_else(|| find(labels.wheel)),
+                pressure_value: 0.0,
+                tilt_value: (0.0, 0.0),
+                rotation_value: 0.0,
+                proximity: true,
+            }),
+            offset,
+        ))
+    }
+}
+
+pub(crate) struct XInput {
+    pub(crate) xi_query_version: extern "C" fn(
+        c: *mut xcb::Connection,
+        major_version: u16,
+        minor_version: u16,
+    ) -> XiQueryVersionCookie,
+    pub(crate) xi_query_version_reply: extern "C" fn(
+        c: *mut xcb::Connection,
+        cookie: XiQueryVersionCookie,
+        e: *mut *mut xcb::GenericError,
+    ) -> *mut XiQueryVersionReply,
+    pub(crate) xi_query_device:
+        extern "C" fn(c: *mut xcb::Connection, deviceid: DeviceId) -> XiQueryDeviceCookie,
+    /// The reply is parsed from its bytes, its layout is variable.
+    pub(crate) xi_query_device_reply: extern "C" fn(
+        c: *mut xcb::Connection,
+        cookie: XiQueryDeviceCookie,
+        e: *mut *mut xcb::GenericError,
+    ) -> *mut xcb::GenericReply,
+    pub(crate) xi_select_events: extern "C" fn(
+        c: *mut xcb::Connection,
+        window: xcb::Window,
+        num_mask: u16,
+        masks: *const EventMask,
+    ) -> xcb::VoidCookie,
+    /// Major opcode of the extension, it is in the `extension` field of its
+    /// generic events.
+    pub(crate) opcode: u8,
+    _lib: Linker,
+}
+
+impl XInput {
+    /// Returns `None` when the X server or the library does not support the
+    /// XInput 2.2.
+    pub(crate) fn new(xcb_lib: &xcb::Xcb, connection: *mut xcb::Connection) -> Option<Self> {
+        let _lib = match Linker::new("libxcb-xinput.so") {
+            Some(l) => l,
+            None => {
+                log_e!("Can not load libxcb-xinput, touch input is disabled.");
+                return None;
+            }
+        };
+        macro_rules! fun {
+            ($f:ident) => {
+                unwrap_f!(_lib.get_function(&concat!("xcb_input_", stringify!($f))))
+            };
+        }
+        // It is a variable, so its symbol is the address of the extension.
+        let id: *mut xcb::Extension = fun!(id);
+        let extension = (xcb_lib.get_extension_data)(connection, id);
+        if extension.is_null() || unsafe { (*extension).present } == 0 {
+            log_e!("XInput extension is not present, touch input is disabled.");
+            return None;
+        }
+        let result = Self {
+            xi_query_version: fun!(xi_query_version),
+            xi_query_version_reply: fun!(xi_query_version_reply),
+            xi_query_device: fun!(xi_query_device),
+            xi_query_device_reply: fun!(xi_query_device_reply),
+            xi_select_events: fun!(xi_select_events),
+            opcode: unsafe { (*extension).major_opcode },
+            _lib,
+        };
+        let cookie = (result.xi_query_version)(connection, MAJOR_VERSION, MINOR_VERSION);
+        let reply = (result.xi_query_version_reply)(connection, cookie, null_mut());
+        if reply.is_null() {
+            log_e!("Can not query the XInput version, touch input is disabled.");
+            return None;
+        }
+        let version = unsafe { ((*reply).major_version, (*reply).minor_version) };
+        unsafe {
+            libc::free(reply as *mut _);
+        }
+        if version < (MAJOR_VERSION, MINOR_VERSION) {
+            log_e!(
+                "XInput {}.{} does not support touches, touch input is disabled.",
+                version.0,
+                version.1
+            );
+            return None;
+        }
+        Some(result)
+    }
+
+    /// Touches of all of the devices, the server stops emulating the pointer
+    /// events from the touches for this window. The crossings of the pointer
+    /// tell the proximity of the pens.
+    pub(crate) fn select_touch_events(
+        &self,
+        connection: *mut xcb::Connection,
+        window: xcb::Window,
+    ) {
+        let mask = EventMask {
+            deviceid: ALL_MASTER_DEVICES,
+            mask_len: 1,
+            mask: (1 << ENTER)
+                | (1 << LEAVE)
+                | (1 << TOUCH_BEGIN)
+                | (1 << TOUCH_UPDATE)
+                | (1 << TOUCH_END),
+        };
+        (self.xi_select_events)(connection, window, 1, &mask);
+    }
+
+    /// Returns the connected pens, the devices that are connected later are
+    /// not tracked.
+    pub(crate) fn query_pens(
+        &self,
+        xcb_lib: &xcb::Xcb,
+        connection: *mut xcb::Connection,
+    ) -> Vec<Pen> {
+        let labels = Labels::new(xcb_lib, connection);
+        let cookie = (self.xi_query_device)(connection, ALL_DEVICES);
+        let reply = (self.xi_query_device_reply)(connection, cookie, null_mut());
+        if reply.is_null() {
+            log_e!("Can not query the XInput devices, pen input is disabled.");
+            return Vec::new();
+        }
+        let mut pens = Vec::new();
+        unsafe {
+            let length = size_of::<xcb::GenericReply>() + (*reply).length as usize * 4;
+            let b = slice::from_raw_parts(reply as *const u8, length);
+            let num_infos = read_u16(b, 8).unwrap_or(0);
+            let mut offset = size_of::<xcb::GenericReply>();
+            for _ in 0..num_infos {
+                match b.get(offset..).and_then(|b| Pen::parse(b, &labels)) {
+                    Some((pen, size)) => {
+                        pens.extend(pen);
+                        offset += size;
+                    }
+                    None => {
+                        log_e!("Malformed XInput device info.");
+                        break;
+                    }
+                }
+            }
+            libc::free(reply as *mut _);
+        }
+        pens
+    }
+
+    /// Events of the pens are selected on their slave devices, so the core
+    /// events of the master pointer are still delivered.
+    pub(crate) fn select_pen_events(
+        &self,
+        connection: *mut xcb::Connection,
+        window: xcb::Window,
+        pens: &[Pen],
+    ) {
+        if pens.is_empty() {
+            return;
+        }
+        let masks: Vec<EventMask> = pens
+            .iter()
+            .map(|p| EventMask {
+                deviceid: p.id,
+                mask_len: 1,
+                mask: (1 << MOTION) | (1 << BUTTON_PRESS) | (1 << BUTTON_RELEASE),
+            })
+            .collect();
+        (self.xi_select_events)(connection, window, masks.len() as u16, masks.as_ptr());
+    }
+}
+
+#[cfg(feature = "debug-derive")]
+impl std::fmt::Debug for XInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "XInput-Library")
+    }
+}
@@ -4,10 +4,13 @@ pub(super) const KEY_RELEASE: c_uint = 3;
 pub(super) const BUTTON_PRESS: c_uint = 4;
 pub(super) const BUTTON_RELEASE: c_uint = 5;
 pub(super) const MOTION_NOTIFY: c_uint = 6;
+pub(super) const EXPOSE: c_uint = 12;
 pub(super) const DESTROY_NOTIFY: c_uint = 17;
 pub(super) const CONFIGURE_NOTIFY: c_uint = 22;
 pub(super) const RESIZE_REQUEST: c_uint = 25;
+pub(super) const PROPERTY_NOTIFY: c_uint = 28;
 pub(super) const CLIENT_MESSAGE: c_uint = 33;
+pub(super) const GE_GENERIC: c_uint = 35;
 pub(super) const KEY_A: u8 = 38;
 pub(super) const KEY_B: u8 = 56;
 pub(super) const KEY_C: u8 = 54;
@@ -0,0 +1,341 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+use {
+    super::event::{Data, Engine, Event, EventCategory, Listener, ListenerHandle, Touch},
+    log::{log_e, result_f},
+    serde::{Deserialize, Serialize},
+    std::{
+        fs::File,
+        io::{self, BufRead, BufReader, BufWriter, ErrorKind, Write},
+        path::Path,
+        sync::{Arc, RwLock},
+        thread::sleep,
+        time::Duration,
+    },
+};
+
+/// A recorded event, time is in seconds since the start of the recording.
+#[derive(Clone, Deserialize)]
+#[cfg_attr(feature = "debug-derive", derive(Debug))]
+pub struct Record {
+    pub time: f64,
+    pub data: Data,
+}
+
+#[derive(Serialize)]
+struct RecordRef<'a> {
+    time: f64,
+    data: &'a Data,
+}
+
+/// Returns false for the data that is regenerated from the recorded inputs.
+fn is_recorded(d: &Data) -> bool {
+    !matches!(
+        d,
+        Data::Action { .. } | Data::Touch(Touch::Gesture { .. }) | Data::Terminate
+    )
+}
+
+/// Quits and lifecycle changes would end or suspend the replaying app.
+fn is_replayed(d: &Data) -> bool {
+    is_recorded(d) && !matches!(d, Data::Quit | Data::Lifecycle(_))
+}
+
+/// A listener that writes the events into a file, one JSON record per line.
+///
+/// Actions and touch gestures are not recorded because the `input::ActionMap`
+/// and the engine regenerate them from the replayed inputs. For capturing
+/// everything it must have the smallest priority among the listeners that
+/// stop the propagation.
+pub struct Recorder {
+    writer: BufWriter<File>,
+    start: Instant,
+    handle: Option<ListenerHandle>,
+}
+
+impl Recorder {
+    /// Created recorder is registered in the engine with the `priority`, it
+    /// stops recording when the returned value is dropped.
+    pub fn new<P: AsRef<Path>>(
+        engine: &Engine,
+        priority: i64,
+        path: P,
+    ) -> io::Result<Arc<RwLock<Self>>> {
+        let result = Arc::new(RwLock::new(Self {
+            writer: BufWriter::new(File::create(path)?),
+            start: Instant::now(),
+            handle: None,
+        }));
+        let l: Arc<RwLock<dyn Listener>> = result.clone();
+        result_f!(result.write()).handle =
+            Some(engine.add(priority, EventCategory::all(), Arc::downgrade(&l)));
+        Ok(result)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    fn write(&mut self, e: &Event) -> io::Result<()> {
+        let r = RecordRef {
+            time: e
+                .get_time()
+                .saturating_duration_since(self.start)
+                .as_secs_f64(),
+            data: e.get_data(),
+        };
+        serde_json::to_writer(&mut self.writer, &r)?;
+        self.writer.write_all(b"\n")
+    }
+}
+
+impl Listener for Recorder {
+    fn on_event(&mut self, e: &Event) -> bool {
+        if is_recorded(e.get_data()) {
+            if let Err(e) = self.write(e) {
+                log_e!("Can not record the event: {}", e);
+            }
+        }
+        false
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "debug-derive", derive(Debug))]
+pub enum Timing {
+    /// Keeps the recorded delays between the events.
+    Original,
+    AsFastAsPossible,
+}
+
+/// Feeds the recorded events back into an engine.
+pub struct Replayer {
+    records: Vec<Record>,
+}
+
+impl Replayer {
+    /// Fails on the records whose time is negative or too large.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut records = Vec::new();
+        for (i, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let r: Record = serde_json::from_str(&line)?;
+            if !(r.time >= 0.0 && r.time < u64::MAX as f64) {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Invalid time {} of the record at line {}", r.time, i + 1),
+                ));
+            }
+            records.push(r);
+        }
+        Ok(Self { records })
+    }
+
+    pub fn get_records(&self) -> &[Record] {
+        &self.records
+    }
+
+    /// Blocks until all of the records are sent to the engine, the engine's
+    /// state is updated the same way as the platform's inputs do. Actions,
+    /// gestures and terminations are skipped because they are not inputs,
+    /// quits and lifecycle changes are skipped too but they are still in the
+    /// `get_records`.
+    pub fn play(&self, engine: &Engine, timing: Timing) {
+        let start = Instant::now();
+        for r in self.records.iter().filter(|r| is_replayed(&r.data)) {
+            if timing == Timing::Original {
+                let time = Duration::from_secs_f64(r.time);
+                let elapsed = start.elapsed();
+                if time > elapsed {
+                    sleep(time - elapsed);
+                }
+            }
+            engine.inject(r.data.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::event::{
+            ActionState, Button, ButtonAction, DispatchMode, GamepadAxis, GamepadButton, Keyboard,
+            Lifecycle, Mouse, PenButton, PenState, TouchAction,
+        },
+        log::unwrap_f,
+        std::{env, fs, path::PathBuf, process, sync::Mutex},
+    };
+
+    fn new_engine() -> Engine {
+        let engine = Engine::new();
+        engine.set_dispatch_mode(DispatchMode::CallerThread);
+        engine.init_window_aspects(200, 100, 1.0);
+        engine
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("record-{}-{}.jsonl", name, process::id()))
+    }
+
+    fn to_json(time: f64, data: &Data) -> String {
+        result_f!(serde_json::to_string(&RecordRef { time, data }))
+    }
+
+    #[test]
+    fn every_recorded_data_survives_the_round_trip() {
+        let engine = new_engine();
+        let path = temp_path("round-trip");
+        let recorder = result_f!(Recorder::new(&engine, 0, &path));
+        let expected = Arc::new(Mutex::new(Vec::new()));
+        let e = expected.clone();
+        let _h = engine.on(1, move |ev| {
+            if is_recorded(ev.get_data()) {
+                result_f!(e.lock()).push(result_f!(serde_json::to_string(ev.get_data())));
+            }
+            false
+        });
+        engine.window_size_changed(300, 150);
+        engine.scale_factor_changed(2.0);
+        engine.window_moved((5, 6));
+        engine.request_redraw();
+        engine.window_focus();
+        engine.window_defocus();
+        engine.set_mouse_position((10, 20));
+        engine.wheel_scrolled((0.0, 1.5));
+        engine.button_pressed(Button::Keyboard(Keyboard::A));
+        engine.button_released(Button::Keyboard(Keyboard::A));
+        engine.button_pressed(Button::Mouse(Mouse::Unknown(9)));
+        engine.finger_down(1, 2, 0, 1.0, 0.0);
+        engine.finger_move(3, 4, 0, 0.5, 0.1);
+        engine.inject(Data::Touch(Touch::Raw {
+            index: 0,
+            action: TouchAction::HardPress,
+            point: (3, 4),
+            normalized_point: (0.03, 0.04),
+            pressure: 2.0,
+            size: 0.1,
+        }));
+        engine.finger_up(3, 4, 0, 0.0, 0.1);
+        engine.pen_changed(PenState {
+            position: (7, 8),
+            pressure: 0.5,
+            tilt: (10.0, -5.0),
+            rotation: 90.0,
+            button: Some((PenButton::Barrel(1), ButtonAction::Press)),
+            eraser: true,
+            proximity: true,
+        });
+        engine.gamepad_connected(1, "Pad".to_string());
+        engine.button_pressed(Button::Gamepad {
+            id: 1,
+            button: GamepadButton::South,
+        });
+        engine.gamepad_axis_changed(1, GamepadAxis::LeftX, 0.5);
+        engine.gamepad_disconnected(1);
+        for &l in &[
+            Lifecycle::Suspended,
+            Lifecycle::Resumed,
+            Lifecycle::SaveState,
+            Lifecycle::LowMemory,
+            Lifecycle::SurfaceCreated,
+            Lifecycle::SurfaceDestroyed,
+        ] {
+            engine.lifecycle_changed(l);
+        }
+        engine.quit();
+        engine.inject(Data::Action {
+            name: "jump".to_string(),
+            state: ActionState::Pressed,
+            value: 1.0,
+        });
+        engine.dispatch();
+        result_f!(result_f!(recorder.write()).flush());
+        let replayer = result_f!(Replayer::load(&path));
+        let _ = fs::remove_file(&path);
+        let loaded: Vec<String> = replayer
+            .get_records()
+            .iter()
+            .map(|r| result_f!(serde_json::to_string(&r.data)))
+            .collect();
+        let expected = result_f!(expected.lock());
+        assert_eq!(expected.len(), 28);
+        assert_eq!(loaded, *expected);
+        assert!(replayer.get_records().iter().all(|r| r.time >= 0.0));
+    }
+
+    #[test]
+    fn replay_skips_the_non_inputs_and_rebuilds_the_state() {
+        let recorded = new_engine();
+        let path = temp_path("replay");
+        let recorder = result_f!(Recorder::new(&recorded, 0, &path));
+        recorded.window_size_changed(300, 150);
+        recorded.button_pressed(Button::Keyboard(Keyboard::A));
+        recorded.gamepad_connected(2, "Pad".to_string());
+        recorded.gamepad_axis_changed(2, GamepadAxis::LeftY, -0.5);
+        recorded.button_pressed(Button::Mouse(Mouse::Left));
+        recorded.lifecycle_changed(Lifecycle::Suspended);
+        recorded.quit();
+        recorded.dispatch();
+        result_f!(result_f!(recorder.write()).flush());
+        let mut lines: Vec<String> = result_f!(fs::read_to_string(&path))
+            .lines()
+            .map(|l| l.to_string())
+            .collect();
+        let last = unwrap_f!(lines.pop());
+        lines.push(to_json(
+            0.0,
+            &Data::Action {
+                name: "jump".to_string(),
+                state: ActionState::Pressed,
+                value: 1.0,
+            },
+        ));
+        lines.push(to_json(0.0, &Data::Terminate));
+        lines.push(last);
+        result_f!(fs::write(&path, lines.join("\n")));
+        let replayer = result_f!(Replayer::load(&path));
+        let _ = fs::remove_file(&path);
+        assert_eq!(replayer.get_records().len(), 9);
+
+        let engine = new_engine();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let e = events.clone();
+        let _h = engine.on(0, move |ev| {
+            result_f!(e.lock()).push(ev.get_data().clone());
+            false
+        });
+        replayer.play(&engine, Timing::AsFastAsPossible);
+        engine.dispatch();
+        let events = result_f!(events.lock());
+        assert_eq!(events.len(), 5);
+        assert!(!events.iter().any(|d| matches!(
+            d,
+            Data::Action { .. } | Data::Terminate | Data::Quit | Data::Lifecycle(_)
+        )));
+        let state = engine.input_state();
+        assert_eq!(state.get_window_aspects().get_width(), 300);
+        assert!(state.is_pressed(&Button::Keyboard(Keyboard::A)));
+        assert!(state.is_pressed(&Button::Mouse(Mouse::Left)));
+        assert_eq!(state.get_gamepads()[&2].get_axis(GamepadAxis::LeftY), -0.5);
+    }
+
+    #[test]
+    fn records_with_invalid_times_are_not_loaded() {
+        for &time in &[-1.0, 1e300, f64::NAN] {
+            let path = temp_path("invalid");
+            result_f!(fs::write(&path, to_json(time, &Data::Quit)));
+            let r = Replayer::load(&path);
+            let _ = fs::remove_file(&path);
+            match r {
+                Err(e) => assert_eq!(e.kind(), ErrorKind::InvalidData),
+                Ok(_) => panic!("Record at {} is loaded", time),
+            }
+        }
+    }
+}
@@ -2,38 +2,145 @@ pub extern crate js_sys;
 pub extern crate wasm_bindgen;
 pub extern crate web_sys;
 
+#[cfg(any(feature = "verbose-log", feature = "gl"))]
+use log::log_i;
 use {
     self::wasm_bindgen::{prelude::*, JsCast},
-    super::super::event::Engine,
-    log::{log_i, result_f, unwrap_f},
+    super::super::event::{
+        Button, ButtonAction, Engine, FingerIndexType, Keyboard, Lifecycle, Mouse, PenButton,
+        PenState,
+    },
+    log::{result_f, unwrap_f},
     std::sync::Arc,
 };
 
+/// The canvas that the window renders into.
+#[derive(Clone)]
+#[cfg_attr(feature = "debug-derive", derive(Debug))]
+pub enum Canvas {
+    /// An existing canvas element with this id.
+    Id(String),
+    Element(web_sys::HtmlCanvasElement),
+    /// A new canvas that fills the viewport, the body's margin is removed.
+    Create,
+}
+
+/// The same framebuffer as the GLX one of the desktop in the defaults.
+#[cfg(feature = "gl")]
+#[derive(Clone)]
+#[cfg_attr(feature = "debug-derive", derive(Debug))]
+pub struct ContextConfig {
+    /// Falls back to the WebGL 1 when the WebGL 2 is not available.
+    pub webgl2: bool,
+    pub alpha: bool,
+    pub depth: bool,
+    pub stencil: bool,
+    /// Multisampling, number of the samples is chosen by the browser.
+    pub antialias: bool,
+    pub premultiplied_alpha: bool,
+    /// False behaves like the swapped double buffers.
+    pub preserve_drawing_buffer: bool,
+}
+
+#[cfg(feature = "gl")]
+impl Default for ContextConfig {
+    fn default() -> Self {
+        Self {
+            webgl2: true,
+            alpha: true,
+            depth: true,
+            stencil: true,
+            antialias: true,
+            premultiplied_alpha: true,
+            preserve_drawing_buffer: false,
+        }
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "debug-derive", derive(Debug))]
+pub struct Config {
+    pub canvas: Canvas,
+    #[cfg(feature = "gl")]
+    pub context: ContextConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            canvas: Canvas::Id("canvas".to_string()),
+            #[cfg(feature = "gl")]
+            context: ContextConfig::default(),
+        }
+    }
+}
+
+#[cfg(feature = "gl")]
+#[derive(Clone)]
+#[cfg_attr(feature = "debug-derive", derive(Debug))]
+pub enum Context {
+    WebGl2(web_sys::WebGl2RenderingContext),
+    WebGl(web_sys::WebGlRenderingContext),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Pointer {
+    Over,
+    Move,
+    Down,
+    Up,
+    Cancel,
+    Leave,
+}
+
 pub struct Window {
     window: web_sys::Window,
     document: web_sys::Document,
     canvas: web_sys::HtmlCanvasElement,
+    #[cfg(feature = "gl")]
+    gl_context: Context,
     event_engine: Engine,
 }
 
 impl Window {
     pub fn new(_: ()) -> Arc<Self> {
+        Self::new_with_config(Config::default())
+    }
+
+    /// Events are queued by the DOM listeners and dispatched in the
+    /// `fetch_events`, the engine can only work in the caller thread mode on
+    /// the web. Sizes and positions are in the physical pixels, the canvas's
+    /// backing store is resized to them.
+    pub fn new_with_config(config: Config) -> Arc<Self> {
         let window = unwrap_f!(web_sys::window());
         let document = unwrap_f!(window.document());
-        let canvas = unwrap_f!(document.get_element_by_id("canvas"));
+        let canvas = Self::get_canvas(&document, config.canvas);
+        // Otherwise the browser pans and zooms the page with the touches.
+        result_f!(canvas.style().set_property("touch-action", "none"));
+        Self::pin_css_size(&canvas);
+        let (width, height, scale_factor) = Self::fit_canvas(&window, &canvas);
         let event_engine = Engine::new();
-        {
-            let e: &web_sys::HtmlElement = unwrap_f!(canvas.dyn_ref());
-            event_engine.init_window_aspects(e.offset_width() as i64, e.offset_height() as i64);
-            event_engine.init_mouse_position((0, 0));
-        }
-        let canvas = result_f!(canvas.dyn_into());
+        event_engine.init_window_aspects(width, height, scale_factor);
+        event_engine.init_window_position((0, 0));
+        event_engine.init_mouse_position((0, 0));
+        #[cfg(feature = "gl")]
+        let gl_context = Self::create_context(&canvas, &config.context);
         let result = Arc::new(Self {
             window,
             document,
             canvas,
+            #[cfg(feature = "gl")]
+            gl_context,
             event_engine,
         });
+        {
+            let win = result.clone();
+            let f = Closure::wrap(Box::new(move || win.resized()) as Box<dyn FnMut()>);
+            let observer = result_f!(web_sys::ResizeObserver::new(f.as_ref().unchecked_ref()));
+            observer.observe(&result.canvas);
+            f.forget()
+        }
+        Self::watch_scale_factor(result.clone());
         macro_rules! set_event {
             ($f1:ident, $f2:ident) => {{
                 let win = result.clone();
@@ -43,18 +150,554 @@ impl Window {
             }};
         }
         set_event!(resized, set_onresize);
-        set_event!(init_mouse, set_onmousemove);
-        set_event!(init_mouse, set_onmouseenter);
+        set_event!(focused, set_onfocus);
+        set_event!(defocused, set_onblur);
+        macro_rules! listen {
+            ($target:ident, $name:expr, $t:ty, $f:ident $(, $arg:expr)*) => {{
+                let win = result.clone();
+                let f = Closure::wrap(
+                    Box::new(move |e: $t| win.$f(&e $(, $arg)*)) as Box<dyn FnMut($t)>
+                );
+                result_f!(result
+                    .$target
+                    .add_event_listener_with_callback($name, f.as_ref().unchecked_ref()));
+                f.forget()
+            }};
+        }
+        listen!(
+            document,
+            "visibilitychange",
+            web_sys::Event,
+            visibility_changed
+        );
+        listen!(window, "keydown", web_sys::KeyboardEvent, key_down);
+        listen!(window, "keyup", web_sys::KeyboardEvent, key_up);
+        for &(name, p) in &[
+            ("pointerover", Pointer::Over),
+            ("pointermove", Pointer::Move),
+            ("pointerdown", Pointer::Down),
+            ("pointerup", Pointer::Up),
+            ("pointercancel", Pointer::Cancel),
+            ("pointerleave", Pointer::Leave),
+        ] {
+            listen!(canvas, name, web_sys::PointerEvent, pointer_event, p);
+        }
+        listen!(canvas, "wheel", web_sys::WheelEvent, wheel_event);
+        listen!(canvas, "contextmenu", web_sys::Event, prevent_default);
         result
     }
 
+    pub fn fetch_events(&self) {
+        self.event_engine.start_frame();
+        self.event_engine.dispatch();
+    }
+
+    pub fn get_event_engine(&self) -> &Engine {
+        &self.event_engine
+    }
+
+    pub fn request_redraw(&self) {
+        self.event_engine.request_redraw();
+    }
+
+    #[cfg(feature = "gl")]
+    pub fn get_gl_context(&self) -> &Context {
+        &self.gl_context
+    }
+
+    /// The browser presents the drawing buffer after each animation frame.
+    #[cfg(feature = "gl")]
+    pub fn swap(&self) {}
+
+    /// The browsers have no function pointers, use the `get_gl_context`.
+    #[cfg(feature = "gl")]
+    pub fn get_gl_function<T>(&self, _: &str) -> Option<T> {
+        None
+    }
+
+    fn get_canvas(document: &web_sys::Document, canvas: Canvas) -> web_sys::HtmlCanvasElement {
+        match canvas {
+            Canvas::Id(id) => result_f!(unwrap_f!(document.get_element_by_id(&id)).dyn_into()),
+            Canvas::Element(e) => e,
+            Canvas::Create => {
+                let e: web_sys::HtmlCanvasElement =
+                    result_f!(result_f!(document.create_element("canvas")).dyn_into());
+                let style = e.style();
+                result_f!(style.set_property("display", "block"));
+                result_f!(style.set_property("width", "100vw"));
+                result_f!(style.set_property("height", "100vh"));
+                let body = unwrap_f!(document.body());
+                result_f!(body.style().set_property("margin", "0"));
+                result_f!(body.append_child(&e));
+                e
+            }
+        }
+    }
+
+    /// A canvas without a CSS size takes its size from the backing store, it
+    /// would grow on every resize, so it keeps its initial size.
+    fn pin_css_size(canvas: &web_sys::HtmlCanvasElement) {
+        let style = canvas.style();
+        let unstyled = result_f!(style.get_property_value("width")).is_empty()
+            && result_f!(style.get_property_value("height")).is_empty();
+        if unstyled
+            && canvas.client_width() as u32 == canvas.width()
+            && canvas.client_height() as u32 == canvas.height()
+        {
+            let width = format!("{}px", canvas.client_width());
+            let height = format!("{}px", canvas.client_height());
+            result_f!(style.set_property("width", &width));
+            result_f!(style.set_property("height", &height));
+        }
+    }
+
+    /// Resizes the backing store to the physical size of the canvas.
+    fn fit_canvas(
+        window: &web_sys::Window,
+        canvas: &web_sys::HtmlCanvasElement,
+    ) -> (i64, i64, f64) {
+        let scale_factor = window.device_pixel_ratio();
+        let width = (canvas.client_width() as f64 * scale_factor).round() as u32;
+        let height = (canvas.client_height() as f64 * scale_factor).round() as u32;
+        if canvas.width() != width {
+            canvas.set_width(width);
+        }
+        if canvas.height() != height {
+            canvas.set_height(height);
+        }
+        (width as i64, height as i64, scale_factor)
+    }
+
+    #[cfg(feature = "gl")]
+    fn create_context(canvas: &web_sys::HtmlCanvasElement, config: &ContextConfig) -> Context {
+        let attributes = js_sys::Object::new();
+        for &(name, value) in &[
+            ("alpha", config.alpha),
+            ("depth", config.depth),
+            ("stencil", config.stencil),
+            ("antialias", config.antialias),
+            ("premultipliedAlpha", config.premultiplied_alpha),
+            ("preserveDrawingBuffer", config.preserve_drawing_buffer),
+        ] {
+            result_f!(js_sys::Reflect::set(
+                &attributes,
+                &JsValue::from_str(name),
+                &JsValue::from_bool(value)
+            ));
+        }
+        if config.webgl2 {
+            let c = result_f!(canvas.get_context_with_context_options("webgl2", &attributes));
+            if let Some(c) = c {
+                return Context::WebGl2(result_f!(c.dyn_into()));
+            }
+            log_i!("WebGL 2 is not available, falling back to WebGL 1.");
+        }
+        let c = result_f!(canvas.get_context_with_context_options("webgl", &attributes));
+        Context::WebGl(result_f!(unwrap_f!(c).dyn_into()))
+    }
+
+    /// Changes of only the device pixel ratio (e.g. the zoom or moving to
+    /// another screen) do not resize the canvas, so the media query of the
+    /// current ratio is watched and renewed after each change.
+    fn watch_scale_factor(win: Arc<Self>) {
+        let query = format!("(resolution: {}dppx)", win.window.device_pixel_ratio());
+        let list = match result_f!(win.window.match_media(&query)) {
+            Some(l) => l,
+            None => return,
+        };
+        let l = list.clone();
+        let f = Closure::once_into_js(move |_: web_sys::Event| {
+            l.set_onchange(None);
+            win.resized();
+            Self::watch_scale_factor(win);
+        });
+        list.set_onchange(Some(f.unchecked_ref()));
+    }
+
     fn resized(&self) {
-        let e: &web_sys::HtmlElement = unwrap_f!(self.canvas.dyn_ref());
+        let (width, height, scale_factor) = Self::fit_canvas(&self.window, &self.canvas);
+        self.event_engine.scale_factor_changed(scale_factor);
+        self.event_engine.window_size_changed(width, height);
+    }
+
+    fn focused(&self) {
+        self.event_engine.window_focus();
+    }
+
+    fn defocused(&self) {
+        self.event_engine.window_defocus();
+    }
+
+    fn visibility_changed(&self, _: &web_sys::Event) {
+        self.event_engine
+            .lifecycle_changed(if self.document.hidden() {
+                Lifecycle::Suspended
+            } else {
+                Lifecycle::Resumed
+            });
+    }
+
+    fn prevent_default(&self, e: &web_sys::Event) {
+        e.prevent_default();
+    }
+
+    fn get_position(&self, e: &web_sys::MouseEvent) -> (i64, i64) {
+        let scale_factor = self.window.device_pixel_ratio();
+        (
+            (e.offset_x() as f64 * scale_factor).round() as i64,
+            (e.offset_y() as f64 * scale_factor).round() as i64,
+        )
+    }
+
+    fn key_down(&self, e: &web_sys::KeyboardEvent) {
+        self.event_engine
+            .button_pressed(Self::translate_key_button(e));
+    }
+
+    fn key_up(&self, e: &web_sys::KeyboardEvent) {
+        self.event_engine
+            .button_released(Self::translate_key_button(e));
+    }
+
+    fn translate_key_button(e: &web_sys::KeyboardEvent) -> Button {
+        Button::Keyboard(Self::translate_key(&e.code(), e.key_code()))
+    }
+
+    fn pointer_event(&self, e: &web_sys::PointerEvent, p: Pointer) {
+        match e.pointer_type().as_str() {
+            "mouse" => self.mouse_event(e, p),
+            "touch" => self.touch_event(e, p),
+            "pen" => self.pen_event(
+                e,
+                match p {
+                    Pointer::Down => Some(ButtonAction::Press),
+                    Pointer::Up => Some(ButtonAction::Release),
+                    _ => None,
+                },
+                p != Pointer::Leave && p != Pointer::Cancel,
+            ),
+            _t => {
+                #[cfg(feature = "verbose-log")]
+                log_i!("Unknown pointer type: {}", _t);
+            }
+        }
+    }
+
+    /// The buttons pressed while another one is held come with the move
+    /// events, so the button's state is read from the `buttons` mask.
+    fn mouse_event(&self, e: &web_sys::PointerEvent, p: Pointer) {
+        match p {
+            Pointer::Over | Pointer::Move => {
+                self.event_engine.set_mouse_position(self.get_position(e))
+            }
+            Pointer::Down => {
+                result_f!(self.canvas.set_pointer_capture(e.pointer_id()));
+            }
+            _ => (),
+        }
+        let (button, mask) = match e.button() {
+            -1 => return,
+            0 => (Mouse::Left, 1),
+            1 => (Mouse::Middle, 4),
+            2 => (Mouse::Right, 2),
+            3 => (Mouse::Back, 8),
+            4 => (Mouse::Forward, 16),
+            b => (
+                Mouse::Unknown(b as u32),
+                1u16.checked_shl(b as u32).unwrap_or(0),
+            ),
+        };
+        if e.buttons() & mask != 0 {
+            self.event_engine.button_pressed(Button::Mouse(button));
+        } else {
+            self.event_engine.button_released(Button::Mouse(button));
+        }
+    }
+
+    /// Size is the contact area relative to the canvas.
+    fn touch_event(&self, e: &web_sys::PointerEvent, p: Pointer) {
+        let (x, y) = self.get_position(e);
+        let index = e.pointer_id() as FingerIndexType;
+        let pressure = e.pressure() as f64;
+        let area = self.canvas.client_width() as f64 * self.canvas.client_height() as f64;
+        let size = if area > 0.0 {
+            e.width() as f64 * e.height() as f64 / area
+        } else {
+            0.0
+        };
+        match p {
+            Pointer::Down => self.event_engine.finger_down(x, y, index, pressure, size),
+            Pointer::Move => self.event_engine.finger_move(x, y, index, pressure, size),
+            Pointer::Up => self.event_engine.finger_up(x, y, index, pressure, size),
+            Pointer::Cancel => self.event_engine.finger_cancel(x, y, index, pressure, size),
+            Pointer::Over | Pointer::Leave => (),
+        }
+    }
+
+    fn pen_event(&self, e: &web_sys::PointerEvent, action: Option<ButtonAction>, proximity: bool) {
+        const ERASER_BUTTON: i16 = 5;
+        const ERASER_BUTTONS: u16 = 32;
+        let button = action.map(|a| {
+            (
+                match e.button() {
+                    0 | ERASER_BUTTON => PenButton::Tip,
+                    2 => PenButton::Barrel(1),
+                    b => PenButton::Barrel(b as u32 + 1),
+                },
+                a,
+            )
+        });
+        self.event_engine.pen_changed(PenState {
+            position: self.get_position(e),
+            pressure: e.pressure() as f64,
+            tilt: (e.tilt_x() as f64, e.tilt_y() as f64),
+            rotation: e.twist() as f64,
+            button,
+            eraser: e.button() == ERASER_BUTTON || e.buttons() & ERASER_BUTTONS != 0,
+            proximity,
+        });
+    }
+
+    /// Deltas in pixels are converted with the usual 100 pixels per step of
+    /// the browsers, a page is taken as 10 steps.
+    fn wheel_event(&self, e: &web_sys::WheelEvent) {
+        const PIXELS_PER_STEP: f64 = 100.0;
+        const LINES_PER_STEP: f64 = 3.0;
+        const STEPS_PER_PAGE: f64 = 10.0;
+        e.prevent_default();
+        let scale = match e.delta_mode() {
+            web_sys::WheelEvent::DOM_DELTA_PIXEL => 1.0 / PIXELS_PER_STEP,
+            web_sys::WheelEvent::DOM_DELTA_LINE => 1.0 / LINES_PER_STEP,
+            _ => STEPS_PER_PAGE,
+        };
         self.event_engine
-            .window_size_changed(e.offset_width() as i64, e.offset_height() as i64);
+            .wheel_scrolled((e.delta_x() * scale, e.delta_y() * scale));
+    }
+
+    /// Keys are mapped by their physical positions, `key_code` is only kept
+    /// for the unknown keys.
+    fn translate_key(code: &str, key_code: u32) -> Keyboard {
+        const LETTERS: [Keyboard; 26] = [
+            Keyboard::A,
+            Keyboard::B,
+            Keyboard::C,
+            Keyboard::D,
+            Keyboard::E,
+            Keyboard::F,
+            Keyboard::G,
+            Keyboard::H,
+            Keyboard::I,
+            Keyboard::J,
+            Keyboard::K,
+            Keyboard::L,
+            Keyboard::M,
+            Keyboard::N,
+            Keyboard::O,
+            Keyboard::P,
+            Keyboard::Q,
+            Keyboard::R,
+            Keyboard::S,
+            Keyboard::T,
+            Keyboard::U,
+            Keyboard::V,
+            Keyboard::W,
+            Keyboard::X,
+            Keyboard::Y,
+            Keyboard::Z,
+        ];
+        let b = code.as_bytes();
+        if b.len() == 4 && code.starts_with("Key") && b[3].is_ascii_uppercase() {
+            return LETTERS[(b[3] - b'A') as usize].clone();
+        }
+        if b.len() == 6 && code.starts_with("Digit") && b[5].is_ascii_digit() {
+            return Keyboard::Number {
+                number: (b[5] - b'0') as i32,
+                pad: false,
+            };
+        }
+        if b.len() == 7 && code.starts_with("Numpad") && b[6].is_ascii_digit() {
+            return Keyboard::Number {
+                number: (b[6] - b'0') as i32,
+                pad: true,
+            };
+        }
+        if code.len() > 1 && code.starts_with('F') {
+            if let Ok(f) = code[1..].parse() {
+                return Keyboard::Function(f);
+            }
+        }
+        match code {
+            "Escape" => Keyboard::Escape(0),
+            "PrintScreen" => Keyboard::PrintScreen,
+            "ScrollLock" => Keyboard::ScrollLock,
+            "Pause" => Keyboard::PauseBreak,
+            "Backquote" => Keyboard::BackQuote,
+            "Backspace" => Keyboard::Backspace,
+            "Delete" => Keyboard::Delete,
+            "Insert" => Keyboard::Insert,
+            "Home" => Keyboard::Home,
+            "End" => Keyboard::End,
+            "PageUp" => Keyboard::PageUp,
+            "PageDown" => Keyboard::PageDown,
+            "NumLock" => Keyboard::NumLock,
+            "Slash" => Keyboard::Slash(0),
+            "NumpadDivide" => Keyboard::Slash(1),
+            "NumpadMultiply" => Keyboard::Star,
+            "NumpadAdd" => Keyboard::Plus(1),
+            "Minus" => Keyboard::Minus(0),
+            "NumpadSubtract" => Keyboard::Minus(1),
+            "Enter" => Keyboard::Enter(0),
+            "NumpadEnter" => Keyboard::Enter(1),
+            "Period" => Keyboard::Dot(0),
+            "NumpadDecimal" => Keyboard::Dot(1),
+            "Tab" => Keyboard::Tab,
+            "BracketLeft" => Keyboard::BracketLeft,
+            "BracketRight" => Keyboard::BracketRight,
+            "CapsLock" => Keyboard::CapsLock(0),
+            "Semicolon" => Keyboard::SemiColon,
+            "Quote" => Keyboard::Quote,
+            "Backslash" => Keyboard::BackSlash(0),
+            "IntlBackslash" => Keyboard::BackSlash(1),
+            "ShiftLeft" => Keyboard::Shift(0),
+            "ShiftRight" => Keyboard::Shift(1),
+            "Comma" => Keyboard::Comma(0),
+            "NumpadComma" => Keyboard::Comma(1),
+            "ControlLeft" => Keyboard::Control(0),
+            "ControlRight" => Keyboard::Control(1),
+            "AltLeft" => Keyboard::Alt(0),
+            "AltRight" => Keyboard::Alt(1),
+            "Space" => Keyboard::Space(0),
+            "MetaLeft" | "OSLeft" => Keyboard::Super(0),
+            "MetaRight" | "OSRight" => Keyboard::Super(1),
+            "ContextMenu" => Keyboard::Menu(0),
+            "ArrowUp" => Keyboard::ArrowUp,
+            "ArrowDown" => Keyboard::ArrowDown,
+            "ArrowLeft" => Keyboard::ArrowLeft,
+            "ArrowRight" => Keyboard::ArrowRight,
+            "Equal" | "NumpadEqual" => Keyboard::Equal,
+            "BrowserBack" => Keyboard::Back,
+            "AudioVolumeUp" => Keyboard::VolumeUp,
+            "AudioVolumeDown" => Keyboard::VolumeDown,
+            "AudioVolumeMute" => Keyboard::VolumeMute,
+            "MediaPlayPause" => Keyboard::MediaPlayPause,
+            "MediaStop" => Keyboard::MediaStop,
+            "MediaTrackNext" => Keyboard::MediaNext,
+            "MediaTrackPrevious" => Keyboard::MediaPrevious,
+            _ => {
+                #[cfg(feature = "verbose-log")]
+                log_i!("Unknown key code: {}", code);
+                Keyboard::Unknown(key_code)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate wasm_bindgen_test;
+
+    use {
+        self::wasm_bindgen_test::*,
+        super::*,
+        crate::event::{Data, EventCategory},
+        std::sync::Mutex,
+    };
+
+    // The window needs a document with a canvas and the pointer events are
+    // constructed through the DOM, neither exists in Node. The configuration
+    // applies to the whole test binary, so the pure key translation runs in
+    // the browser too, e.g. `wasm-pack test --headless --firefox`.
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    fn new_window() -> Arc<Window> {
+        Window::new_with_config(Config {
+            canvas: Canvas::Create,
+            ..Config::default()
+        })
+    }
+
+    fn send(window: &Window, name: &str, pointer_type: &str, id: i32, button: i16, buttons: u16) {
+        let init = web_sys::PointerEventInit::new();
+        init.set_pointer_type(pointer_type);
+        init.set_pointer_id(id);
+        init.set_button(button);
+        init.set_buttons(buttons);
+        init.set_pressure(0.5);
+        let e = result_f!(web_sys::PointerEvent::new_with_event_init_dict(name, &init));
+        result_f!(window.canvas.dispatch_event(&e));
+    }
+
+    #[wasm_bindgen_test]
+    fn keys_are_translated_by_their_codes() {
+        assert!(Window::translate_key("KeyA", 65) == Keyboard::A);
+        assert!(Window::translate_key("KeyZ", 90) == Keyboard::Z);
+        assert!(
+            Window::translate_key("Digit7", 55)
+                == Keyboard::Number {
+                    number: 7,
+                    pad: false,
+                }
+        );
+        assert!(
+            Window::translate_key("Numpad0", 96)
+                == Keyboard::Number {
+                    number: 0,
+                    pad: true,
+                }
+        );
+        assert!(Window::translate_key("F12", 123) == Keyboard::Function(12));
+        assert!(Window::translate_key("ShiftRight", 16) == Keyboard::Shift(1));
+        assert!(Window::translate_key("NumpadEnter", 13) == Keyboard::Enter(1));
+        assert!(Window::translate_key("OSLeft", 91) == Keyboard::Super(0));
+        assert!(Window::translate_key("Keya", 0) == Keyboard::Unknown(0));
+        assert!(Window::translate_key("Lang1", 21) == Keyboard::Unknown(21));
+    }
+
+    #[wasm_bindgen_test]
+    fn mouse_chords_follow_the_buttons_mask() {
+        let window = new_window();
+        let left = Button::Mouse(Mouse::Left);
+        let right = Button::Mouse(Mouse::Right);
+        let pressed = |b: &Button| window.event_engine.input_state().is_pressed(b);
+        send(&window, "pointermove", "mouse", 1, 0, 1);
+        assert!(pressed(&left));
+        send(&window, "pointermove", "mouse", 1, 2, 3);
+        assert!(pressed(&left) && pressed(&right));
+        send(&window, "pointermove", "mouse", 1, -1, 3);
+        assert!(pressed(&left) && pressed(&right));
+        send(&window, "pointermove", "mouse", 1, 0, 2);
+        assert!(!pressed(&left) && pressed(&right));
+        send(&window, "pointerup", "mouse", 1, 2, 0);
+        assert!(!pressed(&right));
+        send(&window, "pointermove", "mouse", 1, 7, 128);
+        assert!(pressed(&Button::Mouse(Mouse::Unknown(7))));
     }
 
-    fn init_mouse(&self) {
-        log_i!("Init mouse")
+    #[wasm_bindgen_test]
+    fn touches_and_pens_are_routed_by_the_pointer_type() {
+        let window = new_window();
+        let pens = Arc::new(Mutex::new(Vec::new()));
+        let p = pens.clone();
+        let _h = window
+            .event_engine
+            .on_category(0, EventCategory::PEN, move |e| {
+                if let Data::Pen { button, .. } = e.get_data() {
+                    result_f!(p.lock()).push(button.is_some());
+                }
+                false
+            });
+        send(&window, "pointerdown", "touch", 5, 0, 1);
+        let state = window.event_engine.input_state();
+        assert!(state.get_touches().contains_key(&5));
+        assert!(state.get_pressed_buttons().is_empty());
+        send(&window, "pointerup", "touch", 5, 0, 0);
+        assert!(window.event_engine.input_state().get_touches().is_empty());
+        send(&window, "pointermove", "pen", 6, -1, 0);
+        send(&window, "pointerdown", "pen", 6, 0, 1);
+        window.fetch_events();
+        assert_eq!(*result_f!(pens.lock()), vec![false, true]);
+        let state = window.event_engine.input_state();
+        assert!(state.get_touches().is_empty());
+        assert!(state.get_pressed_buttons().is_empty());
     }
 }